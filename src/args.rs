@@ -1,48 +1,616 @@
-use paris::Logger;
+use atrium_api::types::string::{Did, Nsid};
+use clap::builder::{RangedU64ValueParser, Resettable};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use scylla::statement::Consistency;
+use std::ffi::OsString;
+use std::path::PathBuf;
 
-#[derive(Debug)]
+/// Every flag falls back to the environment variable named next to it (and `.env`), so
+/// deployments can keep configuring the app through the environment. An empty variable counts
+/// as unset, so `.env` files can list every variable.
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Bluesky Jetstream RPG: experience and levels for Bluesky activity",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    /// Path to the experience rules file (see xp_rules.example.toml). Built-in defaults are used when empty.
+    #[arg(long, env = "XP_RULES_PATH", global = true)]
+    pub xp_rules_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub database: DatabaseSettings,
+
+    #[command(flatten)]
+    pub bsky: BskySettings,
+
+    /// What to run; `run` when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The flags of `run`, used when no command is given.
+    #[command(flatten)]
+    pub run: RunSettings,
+}
+
+/// Settings of the `run` command.
+#[derive(Debug, Args)]
+pub struct RunSettings {
+    #[command(flatten)]
+    pub ingest: AppSettings,
+    #[command(flatten)]
+    pub http: HttpSettings,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the Jetstream ingestion and the HTTP API in one process.
+    Run(RunSettings),
+    /// Run only the HTTP API.
+    Serve {
+        #[command(flatten)]
+        http: HttpSettings,
+    },
+    /// Run only the Jetstream ingestion.
+    Ingest {
+        #[command(flatten)]
+        ingest: AppSettings,
+    },
+    /// Create the keyspace and apply the schema of the models in `src/models/` to it.
+    Migrate {
+        #[command(flatten)]
+        migrate: MigrateSettings,
+    },
+    /// Rebuild a user's experience from the scored records in their repository.
+    Backfill {
+        /// DID of the user, e.g. did:plc:doqrpcaai4iqmkbdo3ztmlld.
+        did: String,
+        #[command(flatten)]
+        backfill: BackfillSettings,
+    },
+    /// Recompute every character's level from its experience counter, e.g. after changing the curve.
+    Recompute,
+    /// Print the character, experience and level of a user.
+    Inspect {
+        /// DID of the user, e.g. did:plc:doqrpcaai4iqmkbdo3ztmlld.
+        did: String,
+    },
+    /// Re-classify likes and reposts that older versions stored as posts (one-off).
+    RepairEventTypes,
+}
+
+/// Jetstream ingestion settings.
+#[derive(Debug, Args)]
 pub struct AppSettings {
-    pub bsky_topics: Vec<String>,
-    pub bsky_dids: Option<Vec<String>>,
+    /// Collections to listen to (comma separated).
+    #[arg(
+        long,
+        env = "BSKY_TOPICS",
+        value_delimiter = ',',
+        default_value = "app.bsky.feed.post",
+        value_parser = nsid
+    )]
+    pub bsky_topics: Vec<Nsid>,
+
+    /// Only listen to these users (comma separated); every user when empty.
+    #[arg(long, env = "BSKY_DIDS", value_delimiter = ',', value_parser = did)]
+    pub bsky_dids: Vec<Did>,
+
+    /// Amount of events processed concurrently.
+    #[arg(long, env = "MAX_WORKERS", default_value_t = 5, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
     pub max_workers: usize,
+
+    /// Name of this ingestion instance, used as the key for the persisted Jetstream cursor.
+    #[arg(long, env = "INSTANCE_ID", default_value = "default")]
+    pub instance_id: String,
+
+    /// Seconds to rewind the persisted cursor on every (re)connect; replayed events are only applied once.
+    #[arg(long, env = "CURSOR_REWIND_SECONDS", default_value_t = 10)]
+    pub cursor_rewind_seconds: u64,
+
+    /// How often (in seconds) the last processed cursor is persisted, also while no events arrive.
+    #[arg(long, env = "CURSOR_CHECKPOINT_SECONDS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    pub cursor_checkpoint_seconds: u64,
+
+    /// Extra Jetstream endpoints (comma separated) tried after the official ones when reconnecting.
+    #[arg(long, env = "JETSTREAM_ENDPOINTS", value_delimiter = ',')]
+    pub jetstream_endpoints: Vec<String>,
+
+    /// First reconnect delay in milliseconds, doubled per attempt.
+    #[arg(long, env = "RECONNECT_BASE_DELAY_MS", default_value_t = 500)]
+    pub reconnect_base_delay_ms: u64,
+
+    /// Maximum reconnect delay in seconds.
+    #[arg(long, env = "RECONNECT_MAX_DELAY_SECONDS", default_value_t = 60)]
+    pub reconnect_max_delay_seconds: u64,
+
+    /// Seconds without any event after which the connection counts as lost and is reopened; 0 disables it.
+    #[arg(long, env = "JETSTREAM_IDLE_TIMEOUT_SECONDS", default_value_t = 60)]
+    pub jetstream_idle_timeout_seconds: u64,
+
+    /// How often (in seconds) characters created without their profile are completed; 0 disables it.
+    #[arg(long, env = "BOOTSTRAP_RECONCILE_INTERVAL_SECONDS", default_value_t = 300)]
+    pub bootstrap_reconcile_interval_seconds: u64,
+
+    /// Seconds to wait for in-flight events on shutdown before giving up.
+    #[arg(long, env = "SHUTDOWN_TIMEOUT_SECONDS", default_value_t = 30)]
+    pub shutdown_timeout_seconds: u64,
 }
 
-impl AppSettings {
-    pub fn new() -> Self {
-        Logger::new();
-        env_logger::init();
-        dotenvy::from_filename(".env").expect("Failed to load .env file");
-        
-        let bsky_topics = dotenvy::var("BSKY_TOPICS")
-            .unwrap_or("app.bsky.feed.post".to_string())
-            .split(',')
-            .map(|s| s.to_string())
-            .collect();
+/// ScyllaDB connection settings, shared by every command.
+#[derive(Debug, Args)]
+pub struct DatabaseSettings {
+    /// Contact points of the cluster (comma separated host:port).
+    #[arg(
+        long,
+        env = "SCYLLA_NODES",
+        value_delimiter = ',',
+        default_values = ["localhost:19042", "localhost:19043", "localhost:19044"],
+        global = true
+    )]
+    pub scylla_nodes: Vec<String>,
+
+    /// Keyspace holding the game tables.
+    #[arg(long, env = "SCYLLA_KEYSPACE", default_value = "bsky_rpg", global = true)]
+    pub scylla_keyspace: String,
+
+    /// Prefer nodes of this datacenter (token and datacenter aware load balancing).
+    #[arg(long, env = "SCYLLA_DATACENTER", global = true)]
+    pub scylla_datacenter: Option<String>,
+
+    /// Username for password authentication.
+    #[arg(long, env = "SCYLLA_USERNAME", global = true)]
+    pub scylla_username: Option<String>,
+
+    /// Password for password authentication.
+    #[arg(long, env = "SCYLLA_PASSWORD", global = true, hide_env_values = true)]
+    pub scylla_password: Option<String>,
+
+    /// Connect to the cluster over TLS.
+    #[arg(long, env = "SCYLLA_TLS", global = true)]
+    pub scylla_tls: bool,
+
+    /// CA certificate (PEM) used to verify the nodes; the system roots are used when empty.
+    #[arg(long, env = "SCYLLA_TLS_CA_FILE", global = true)]
+    pub scylla_tls_ca_file: Option<PathBuf>,
+
+    /// Timeout for establishing a connection, in milliseconds.
+    #[arg(long, env = "SCYLLA_CONNECTION_TIMEOUT_MS", default_value_t = 5000, global = true)]
+    pub scylla_connection_timeout_ms: u64,
+
+    /// Timeout for a single request, in milliseconds.
+    #[arg(long, env = "SCYLLA_REQUEST_TIMEOUT_MS", default_value_t = 10000, global = true)]
+    pub scylla_request_timeout_ms: u64,
+
+    /// Consistency of reads.
+    #[arg(long, env = "SCYLLA_READ_CONSISTENCY", value_enum, ignore_case = true, default_value_t = ConsistencyLevel::LocalQuorum, global = true)]
+    pub scylla_read_consistency: ConsistencyLevel,
+
+    /// Consistency of inserts, updates and deletes.
+    #[arg(long, env = "SCYLLA_WRITE_CONSISTENCY", value_enum, ignore_case = true, default_value_t = ConsistencyLevel::LocalQuorum, global = true)]
+    pub scylla_write_consistency: ConsistencyLevel,
+
+    /// Consistency of experience counter updates.
+    #[arg(long, env = "SCYLLA_COUNTER_CONSISTENCY", value_enum, ignore_case = true, default_value_t = ConsistencyLevel::LocalQuorum, global = true)]
+    pub scylla_counter_consistency: ConsistencyLevel,
+
+    /// Amount of prepared statements kept in the statement cache.
+    #[arg(long, env = "SCYLLA_CACHE_SIZE", default_value_t = 50, global = true)]
+    pub scylla_cache_size: usize,
+}
+
+/// Bluesky AppView settings.
+#[derive(Debug, Args)]
+pub struct BskySettings {
+    /// Base URL of the AppView serving profiles and records, e.g. a local mock.
+    #[arg(
+        long,
+        env = "BSKY_APPVIEW_URL",
+        default_value = "https://public.api.bsky.app",
+        global = true
+    )]
+    pub bsky_appview_url: String,
+
+    /// Directory of profile JSON files (one `getProfile` response each) used instead of the AppView.
+    #[arg(long, env = "BSKY_PROFILE_FIXTURES", global = true)]
+    pub bsky_profile_fixtures: Option<PathBuf>,
+
+    /// Milliseconds profile lookups are collected for one `getProfiles` request; 0 fetches them one by one.
+    #[arg(long, env = "BSKY_PROFILE_BATCH_WINDOW_MS", default_value_t = 50, global = true)]
+    pub bsky_profile_batch_window_ms: u64,
+
+    /// Most profiles per `getProfiles` request (the AppView allows up to 25).
+    #[arg(long, env = "BSKY_PROFILE_BATCH_SIZE", default_value_t = 25, global = true)]
+    pub bsky_profile_batch_size: usize,
+
+    /// Profiles kept in the in-process cache; 0 disables the cache.
+    #[arg(long, env = "BSKY_PROFILE_CACHE_CAPACITY", default_value_t = 10000, global = true)]
+    pub bsky_profile_cache_capacity: usize,
+
+    /// Seconds a cached profile is used before it is fetched again.
+    #[arg(long, env = "BSKY_PROFILE_CACHE_TTL_SECONDS", default_value_t = 3600, global = true)]
+    pub bsky_profile_cache_ttl_seconds: u64,
+
+    /// Retries of profile lookups failing with a network error, a 5xx or a 429 response.
+    #[arg(long, env = "BSKY_MAX_RETRIES", default_value_t = 3, global = true)]
+    pub bsky_max_retries: u32,
+
+    /// First retry delay in milliseconds, doubled per retry up to the max (jittered).
+    #[arg(long, env = "BSKY_RETRY_BASE_DELAY_MS", default_value_t = 250, global = true)]
+    pub bsky_retry_base_delay_ms: u64,
+
+    /// Upper bound of a single retry delay, in milliseconds.
+    #[arg(long, env = "BSKY_RETRY_MAX_DELAY_MS", default_value_t = 5000, global = true)]
+    pub bsky_retry_max_delay_ms: u64,
+}
+
+/// Historical backfill settings.
+#[derive(Debug, Args)]
+pub struct BackfillSettings {
+    /// PDS to list the user's records from; resolved from their DID document when empty.
+    #[arg(long, env = "BACKFILL_PDS_URL")]
+    pub pds_url: Option<String>,
+
+    /// PLC directory resolving did:plc identities.
+    #[arg(long, env = "PLC_DIRECTORY_URL", default_value = "https://plc.directory")]
+    pub plc_directory_url: String,
+
+    /// Seconds after its last cursor checkpoint an ingestion instance counts as stopped; the backfill refuses to
+    /// run while one is ingesting.
+    #[arg(long, env = "BACKFILL_INGESTION_TIMEOUT_SECONDS", default_value_t = 30)]
+    pub ingestion_timeout_seconds: u64,
+}
+
+/// CQL consistency levels that make sense for regular (non-LWT) statements.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum ConsistencyLevel {
+    Any,
+    One,
+    Two,
+    Three,
+    Quorum,
+    All,
+    LocalQuorum,
+    EachQuorum,
+    LocalOne,
+}
+
+impl From<ConsistencyLevel> for Consistency {
+    fn from(level: ConsistencyLevel) -> Self {
+        match level {
+            ConsistencyLevel::Any => Consistency::Any,
+            ConsistencyLevel::One => Consistency::One,
+            ConsistencyLevel::Two => Consistency::Two,
+            ConsistencyLevel::Three => Consistency::Three,
+            ConsistencyLevel::Quorum => Consistency::Quorum,
+            ConsistencyLevel::All => Consistency::All,
+            ConsistencyLevel::LocalQuorum => Consistency::LocalQuorum,
+            ConsistencyLevel::EachQuorum => Consistency::EachQuorum,
+            ConsistencyLevel::LocalOne => Consistency::LocalOne,
+        }
+    }
+}
+
+/// Schema migration settings.
+#[derive(Debug, Args)]
+pub struct MigrateSettings {
+    /// Only print the CQL the migration would run.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Replication strategy of the keyspace, when it has to be created.
+    #[arg(long, env = "SCYLLA_REPLICATION_STRATEGY", value_enum, default_value_t = ReplicationStrategy::Simple)]
+    pub replication_strategy: ReplicationStrategy,
+
+    /// Replication factor of the keyspace, when it has to be created.
+    #[arg(long, env = "SCYLLA_REPLICATION_FACTOR", default_value_t = 3)]
+    pub replication_factor: u32,
+
+    /// Per-datacenter replication factors for network_topology (comma separated datacenter:factor).
+    #[arg(long, env = "SCYLLA_DATACENTER_REPLICATION", value_delimiter = ',', value_parser = datacenter_replication)]
+    pub datacenter_replication: Vec<DatacenterReplication>,
+
+    /// Write the migrated schema to this file, e.g. current_schema.json.
+    #[arg(long, env = "SCYLLA_SCHEMA_JSON")]
+    pub schema_json: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum ReplicationStrategy {
+    Simple,
+    NetworkTopology,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatacenterReplication {
+    pub name: String,
+    pub replication_factor: u32,
+}
+
+fn datacenter_replication(value: &str) -> Result<DatacenterReplication, String> {
+    let (name, replication_factor) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected datacenter:factor, got {value:?}"))?;
+    let replication_factor = replication_factor
+        .parse()
+        .map_err(|e| format!("invalid replication factor for {name}: {e}"))?;
 
-        let bsky_dids = dotenvy::var("BSKY_DIDS")
-            .unwrap();
-        
-        let max_workers = dotenvy::var("MAX_WORKERS")
-            .unwrap_or("5".to_string())
-            .parse::<usize>()
-            .expect("Failed to parse WORKERS");
-
-        let bsky_dids: Option<Vec<String>> = if !bsky_dids.is_empty() {
-            let dids = bsky_dids
-                .split(',')
-                .map(|s| s.to_string())
-                .collect();
-
-            Some(dids)
+    Ok(DatacenterReplication {
+        name: name.to_string(),
+        replication_factor,
+    })
+}
+
+/// HTTP API settings.
+#[derive(Debug, Args)]
+pub struct HttpSettings {
+    /// Address the HTTP API listens on.
+    #[arg(long, env = "HTTP_HOST", default_value = "0.0.0.0")]
+    pub http_host: String,
+
+    /// Port the HTTP API listens on.
+    #[arg(long, env = "HTTP_PORT", default_value_t = 8000)]
+    pub http_port: u16,
+
+    /// Amount of HTTP worker threads.
+    #[arg(long, env = "HTTP_WORKERS", default_value_t = 1, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub http_workers: usize,
+}
+
+impl Cli {
+    /// Parses the command line, reading missing flags from the environment and an optional `.env` file.
+    pub fn load() -> Self {
+        dotenvy::dotenv().ok();
+
+        Self::parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    fn parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = without_empty_env(Self::command()).try_get_matches_from(args)?;
+        Self::from_arg_matches(&matches)
+    }
+}
+
+/// Drops the environment fallback of the flags whose variable is set but empty (e.g.
+/// `XP_RULES_PATH=""` in `.env`), so those flags keep their default.
+fn without_empty_env(command: clap::Command) -> clap::Command {
+    let command = command.mut_args(|arg| {
+        let is_empty = arg
+            .get_env()
+            .and_then(std::env::var_os)
+            .is_some_and(|value| value.is_empty());
+        if is_empty {
+            arg.env(Resettable::Reset)
         } else {
-            None
-        };
+            arg
+        }
+    });
+
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    subcommands
+        .into_iter()
+        .fold(command, |command, name| command.mut_subcommand(name, without_empty_env))
+}
 
+fn nsid(value: &str) -> Result<Nsid, String> {
+    Nsid::new(value.to_string()).map_err(|e| format!("{e}: {value:?}"))
+}
 
-        Self {
-            bsky_topics,
-            bsky_dids,
-            max_workers
+fn did(value: &str) -> Result<Did, String> {
+    Did::new(value.to_string()).map_err(|e| format!("{e}: {value:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+    use std::sync::Mutex;
+
+    /// The tests setting environment variables, which are shared by the whole process.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::parse_args(std::iter::once("bsky-rpg").chain(args.iter().copied()))
+    }
+
+    fn command(args: &[&str]) -> Command {
+        parse(args).unwrap().command.unwrap()
+    }
+
+    /// Runs `test` with the environment variables `vars` set.
+    fn with_env(vars: &[(&str, &str)], test: impl FnOnce()) {
+        let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (name, value) in vars {
+            std::env::set_var(name, value);
         }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(test));
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        result.unwrap();
+    }
+
+    #[test]
+    fn without_a_command_the_flags_are_those_of_run() {
+        let cli = parse(&["--max-workers", "20", "--http-port", "9000"]).unwrap();
+
+        assert!(cli.command.is_none());
+        assert_eq!(cli.run.ingest.max_workers, 20);
+        assert_eq!(cli.run.ingest.bsky_topics, [nsid("app.bsky.feed.post").unwrap()]);
+        assert_eq!(cli.run.http.http_port, 9000);
+    }
+
+    #[test]
+    fn run_serve_and_ingest_take_their_service_flags() {
+        let Command::Run(run) = command(&["run", "--max-workers", "20", "--http-workers", "4"]) else {
+            panic!("expected run");
+        };
+        assert_eq!(run.ingest.max_workers, 20);
+        assert_eq!(run.http.http_workers, 4);
+
+        let Command::Serve { http } = command(&["serve", "--http-host", "127.0.0.1"]) else {
+            panic!("expected serve");
+        };
+        assert_eq!(http.http_host, "127.0.0.1");
+        assert!(parse(&["serve", "--max-workers", "20"]).is_err());
+
+        let Command::Ingest { ingest } =
+            command(&["ingest", "--bsky-topics", "app.bsky.feed.post,app.bsky.feed.like"])
+        else {
+            panic!("expected ingest");
+        };
+        let topics: Vec<_> = ingest.bsky_topics.iter().map(|topic| topic.as_str()).collect();
+        assert_eq!(topics, ["app.bsky.feed.post", "app.bsky.feed.like"]);
+        assert!(parse(&["ingest", "--http-port", "9000"]).is_err());
+    }
+
+    #[test]
+    fn migrate_parses_the_replication() {
+        let Command::Migrate { migrate } = command(&[
+            "migrate",
+            "--dry-run",
+            "--replication-strategy",
+            "network_topology",
+            "--datacenter-replication",
+            "dc1:3,dc2:2",
+        ]) else {
+            panic!("expected migrate");
+        };
+
+        assert!(migrate.dry_run);
+        assert!(matches!(migrate.replication_strategy, ReplicationStrategy::NetworkTopology));
+        let replication: Vec<_> = migrate
+            .datacenter_replication
+            .iter()
+            .map(|dc| (dc.name.as_str(), dc.replication_factor))
+            .collect();
+        assert_eq!(replication, [("dc1", 3), ("dc2", 2)]);
+
+        let error = parse(&["migrate", "--datacenter-replication", "dc1"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn the_maintenance_commands_take_their_arguments() {
+        let Command::Backfill { did, backfill } =
+            command(&["backfill", "did:plc:someone", "--pds-url", "https://pds.example"])
+        else {
+            panic!("expected backfill");
+        };
+        assert_eq!(did, "did:plc:someone");
+        assert_eq!(backfill.pds_url.as_deref(), Some("https://pds.example"));
+        assert_eq!(backfill.plc_directory_url, "https://plc.directory");
+        assert_eq!(backfill.ingestion_timeout_seconds, 30);
+
+        let Command::Inspect { did } = command(&["inspect", "did:plc:someone"]) else {
+            panic!("expected inspect");
+        };
+        assert_eq!(did, "did:plc:someone");
+        assert!(parse(&["inspect"]).is_err());
+
+        assert!(matches!(command(&["recompute"]), Command::Recompute));
+        assert!(matches!(command(&["repair-event-types"]), Command::RepairEventTypes));
+    }
+
+    #[test]
+    fn shared_flags_go_after_the_command() {
+        let cli = parse(&["inspect", "did:plc:someone", "--scylla-keyspace", "test"]).unwrap();
+        assert_eq!(cli.database.scylla_keyspace, "test");
+
+        let error = parse(&["--scylla-keyspace", "test", "inspect", "did:plc:someone"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn malformed_dids_and_topics_are_usage_errors() {
+        let error = parse(&["ingest", "--bsky-dids", "did:plc:a,alice.bsky.social"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+
+        let error = parse(&["ingest", "--bsky-topics", "feed post"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn worker_counts_must_be_positive() {
+        let error = parse(&["run", "--max-workers", "0"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+
+        let error = parse(&["serve", "--http-workers", "0"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn missing_flags_fall_back_to_the_environment() {
+        with_env(
+            &[
+                ("MAX_WORKERS", "42"),
+                ("SCYLLA_DATACENTER", "eu-west"),
+                ("SCYLLA_WRITE_CONSISTENCY", "ONE"),
+                ("BSKY_DIDS", "did:plc:a,did:plc:b"),
+                ("BACKFILL_PDS_URL", "https://pds.example"),
+            ],
+            || {
+                let cli = parse(&[]).unwrap();
+                assert_eq!(cli.run.ingest.max_workers, 42);
+                let dids: Vec<_> = cli.run.ingest.bsky_dids.iter().map(|did| did.as_str()).collect();
+                assert_eq!(dids, ["did:plc:a", "did:plc:b"]);
+                assert_eq!(cli.database.scylla_datacenter.as_deref(), Some("eu-west"));
+                assert!(matches!(cli.database.scylla_write_consistency, ConsistencyLevel::One));
+
+                // flags win over the environment
+                let Command::Ingest { ingest } = command(&["ingest", "--max-workers", "3"]) else {
+                    panic!("expected ingest");
+                };
+                assert_eq!(ingest.max_workers, 3);
+
+                let Command::Backfill { backfill, .. } = command(&["backfill", "did:plc:someone"]) else {
+                    panic!("expected backfill");
+                };
+                assert_eq!(backfill.pds_url.as_deref(), Some("https://pds.example"));
+            },
+        );
+    }
+
+    #[test]
+    fn empty_variables_count_as_unset() {
+        with_env(
+            &[
+                ("XP_RULES_PATH", ""),
+                ("MAX_WORKERS", ""),
+                ("BSKY_DIDS", ""),
+                ("SCYLLA_DATACENTER", ""),
+                ("SCYLLA_TLS_CA_FILE", ""),
+                ("SCYLLA_SCHEMA_JSON", ""),
+                ("BACKFILL_PDS_URL", ""),
+            ],
+            || {
+                let cli = parse(&[]).unwrap();
+                assert_eq!(cli.xp_rules_path, None);
+                assert_eq!(cli.run.ingest.max_workers, 5);
+                assert!(cli.run.ingest.bsky_dids.is_empty());
+                assert_eq!(cli.database.scylla_datacenter, None);
+                assert_eq!(cli.database.scylla_tls_ca_file, None);
+
+                let Command::Migrate { migrate } = command(&["migrate"]) else {
+                    panic!("expected migrate");
+                };
+                assert_eq!(migrate.schema_json, None);
+
+                let Command::Backfill { backfill, .. } = command(&["backfill", "did:plc:someone"]) else {
+                    panic!("expected backfill");
+                };
+                assert_eq!(backfill.pds_url, None);
+            },
+        );
+    }
+}
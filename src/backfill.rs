@@ -0,0 +1,588 @@
+use crate::args::{BackfillSettings, BskySettings};
+use crate::events::create::select_event_handler;
+use crate::events::dto::NewEventDTO;
+use crate::events::received::is_received;
+use crate::events::AppBskyEventRecord;
+use crate::leveling::calculate_experience;
+use crate::models::character::Character;
+use crate::models::character_experience::CharacterExperience;
+use crate::models::ingestion_cursor::IngestionCursor;
+use crate::models::pending_bootstrap::PendingBootstrap;
+use crate::repositories::bsky_repository::{BskyRepository, RetryPolicy};
+use crate::repositories::DatabaseRepository;
+use crate::rules::RulesStore;
+use anyhow::Context;
+use atrium_api::record::KnownRecord;
+use atrium_api::types::TryFromUnknown;
+use charybdis::types::Counter;
+use chrono::Utc;
+use futures::TryStreamExt;
+use paris::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where the backfill reads a user's records from.
+#[async_trait::async_trait]
+pub trait RecordSource {
+    /// Every record of `collection` in the repository of `user_did`, with its rkey.
+    async fn list_records(
+        &self,
+        user_did: &str,
+        collection: &str,
+    ) -> anyhow::Result<Vec<(String, KnownRecord)>>;
+}
+
+#[async_trait::async_trait]
+impl RecordSource for BskyRepository {
+    async fn list_records(
+        &self,
+        user_did: &str,
+        collection: &str,
+    ) -> anyhow::Result<Vec<(String, KnownRecord)>> {
+        let mut records = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let page = BskyRepository::list_records(self, user_did, collection, cursor)
+                .await
+                .with_context(|| {
+                    format!("failed to list the {} records of {}", collection, user_did)
+                })?;
+
+            for record in page.data.records {
+                let rkey = record
+                    .uri
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+                match KnownRecord::try_from_unknown(record.data.value) {
+                    Ok(known) => records.push((rkey, known)),
+                    Err(e) => warn!("Skipping unreadable record {}: {}", record.data.uri, e),
+                }
+            }
+
+            match page.data.cursor {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => return Ok(records),
+            }
+        }
+    }
+}
+
+/// What a backfill replayed.
+#[derive(Debug, Default, PartialEq)]
+pub struct BackfillSummary {
+    pub records: usize,
+    pub new_events: usize,
+    pub experience: i64,
+}
+
+/// Backfills `user_did` from the repository on its PDS. Returns `false` if it failed.
+pub async fn run_backfill(
+    repository: &Arc<DatabaseRepository>,
+    rules: &RulesStore,
+    user_did: String,
+    settings: &BackfillSettings,
+    bsky: &BskySettings,
+) -> bool {
+    let timeout = Duration::from_secs(settings.ingestion_timeout_seconds);
+    if let Err(e) = ensure_ingestion_stopped(repository, timeout).await {
+        eprintln!("Not backfilling user {}: {:#}", user_did, e);
+        return false;
+    }
+
+    let pds_url = match &settings.pds_url {
+        Some(pds_url) => pds_url.clone(),
+        None => match resolve_pds(&user_did, &settings.plc_directory_url).await {
+            Ok(pds_url) => pds_url,
+            Err(e) => {
+                eprintln!("Failed to find the PDS of user {}: {:#}", user_did, e);
+                return false;
+            }
+        },
+    };
+    info!("Backfilling user {} from {}", user_did, pds_url);
+
+    let source = BskyRepository::new(pds_url, RetryPolicy::from(bsky));
+    match backfill_character(repository, rules, user_did.clone(), &source).await {
+        Ok(summary) => {
+            info!(
+                "Backfilled user {}: {} records, {} new events, {} experience",
+                user_did, summary.records, summary.new_events, summary.experience
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to backfill user {}: {:#}", user_did, e);
+            false
+        }
+    }
+}
+
+/// Fails while an ingestion instance checkpointed its cursor within `timeout` (ingestion does so
+/// even while no events arrive). The backfill overwrites the experience counter and isn't queued
+/// behind the user's live events, so it must not run alongside ingestion.
+pub async fn ensure_ingestion_stopped(
+    repository: &Arc<DatabaseRepository>,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let cursors: Vec<IngestionCursor> = repository.cursor.find_all_cursors().await.try_collect().await?;
+
+    for cursor in cursors {
+        // a checkpoint from the future (clock skew) counts as just now
+        let idle = (Utc::now() - cursor.updated_at).to_std().unwrap_or_default();
+        if idle < timeout {
+            anyhow::bail!(
+                "ingestion instance {} saved its cursor {}s ago; stop ingestion and wait {}s after it stopped",
+                cursor.instance_id,
+                idle.as_secs(),
+                timeout.as_secs()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the character of `user_did` from every scored record in its repository.
+///
+/// Each record is scored by the same handler as its live event, oldest first. Records that
+/// were never stored get their event; every record's lookup row is (re)written with its score.
+/// The experience counter is then set to exactly the sum of the scores, so the character ends
+/// up where live tracking from the account's first record would have put it. Running it again
+/// changes nothing.
+///
+/// Ingestion must be stopped (see [`ensure_ingestion_stopped`]): events of the user ingested while
+/// the backfill runs would be lost from the counter.
+pub async fn backfill_character(
+    repository: &Arc<DatabaseRepository>,
+    rules: &RulesStore,
+    user_did: String,
+    source: &(dyn RecordSource + Send + Sync),
+) -> anyhow::Result<BackfillSummary> {
+    let profile = repository
+        .profiles
+        .get_author_profile(user_did.clone())
+        .await?;
+
+    let mut scored = Vec::new();
+    for collection in AppBskyEventRecord::ALL {
+        let collection = collection.to_string();
+
+        for (rkey, record) in source.list_records(&user_did, &collection).await? {
+            let Some(handler) = select_event_handler(&record, rules) else {
+                continue;
+            };
+            let posted_at = created_at_us(&record).unwrap_or_default();
+            let payload =
+                NewEventDTO::from_record(user_did.clone(), &collection, rkey, &record, posted_at);
+            let experience = handler.calculate_exp(&payload);
+
+            scored.push((payload, experience));
+        }
+    }
+    scored.sort_by_key(|(payload, _)| payload.posted_at);
+
+    let current_rules = rules.current();
+    let mut summary = BackfillSummary::default();
+
+    for (payload, experience) in &scored {
+        let previous_experience = summary.experience;
+        summary.experience = summary.experience.saturating_add(*experience as i64);
+        summary.records += 1;
+
+        let already_stored = repository
+            .event
+            .find_event_record(
+                user_did.clone(),
+                payload.event_type.clone(),
+                payload.event_id.clone(),
+            )
+            .await
+            .is_some();
+
+        if !already_stored {
+            let leveling_response_dto = calculate_experience(
+                current_rules.curve(),
+                previous_experience,
+                summary.experience,
+            );
+            repository
+                .event
+                .insert_event(payload, leveling_response_dto)
+                .await;
+            summary.new_events += 1;
+        }
+
+        repository
+            .event
+            .insert_event_record(payload, *experience)
+            .await;
+    }
+
+    // engagement received from other accounts isn't in the user's repository, keep it
+    let received_experience = repository
+        .event
+        .find_event_records(user_did.clone())
+        .await
+        .try_fold(0_i64, |total, record| async move {
+            if is_received(&record.event_type) {
+                Ok(total.saturating_add(record.experience_gained as i64))
+            } else {
+                Ok(total)
+            }
+        })
+        .await?;
+    summary.experience = summary.experience.saturating_add(received_experience);
+
+    set_experience(repository, &user_did, summary.experience).await;
+
+    let mut character = match repository
+        .character
+        .find_by_partition_key(user_did.clone())
+        .await
+    {
+        Some(character) => character,
+        None => Character::from_profile(profile.clone(), &current_rules),
+    };
+    character.name = profile.handle.to_string();
+    character.display_name = profile.display_name.clone().unwrap_or_default();
+    character.description = profile.description.clone().unwrap_or_default();
+    character.bootstrap_pending = Some(false);
+
+    let leveling_response_dto = calculate_experience(
+        current_rules.curve(),
+        summary.experience,
+        summary.experience,
+    );
+    repository
+        .character
+        .update_character(&mut character, leveling_response_dto)
+        .await;
+
+    // the history replaces any approximated bootstrap
+    repository
+        .character
+        .delete_pending_bootstrap(&PendingBootstrap {
+            user_did,
+            ..Default::default()
+        })
+        .await;
+
+    Ok(summary)
+}
+
+/// Moves the counter of `user_did` to exactly `experience`.
+async fn set_experience(repository: &Arc<DatabaseRepository>, user_did: &str, experience: i64) {
+    let current_experience = repository
+        .experience
+        .find_character_experience_by_partition_key(user_did.to_string())
+        .await
+        .map(|character_experience| character_experience.current_experience.0)
+        .unwrap_or_default();
+
+    let character_experience = CharacterExperience {
+        user_did: user_did.to_string(),
+        current_experience: Counter(0),
+    };
+    let difference = experience.saturating_sub(current_experience);
+
+    if difference > 0 {
+        repository
+            .experience
+            .increment_character_experience(character_experience, difference)
+            .await;
+    } else if difference < 0 {
+        repository
+            .experience
+            .decrement_character_experience(character_experience, -difference)
+            .await;
+    }
+}
+
+/// The record's own creation time, in the microseconds live events are stored with.
+fn created_at_us(record: &KnownRecord) -> Option<u64> {
+    let created_at = match record {
+        KnownRecord::AppBskyFeedPost(post) => &post.created_at,
+        KnownRecord::AppBskyFeedLike(like) => &like.created_at,
+        KnownRecord::AppBskyFeedRepost(repost) => &repost.created_at,
+        KnownRecord::AppBskyGraphFollow(follow) => &follow.created_at,
+        KnownRecord::AppBskyGraphBlock(block) => &block.created_at,
+        KnownRecord::AppBskyGraphListitem(list_item) => &list_item.created_at,
+        KnownRecord::AppBskyGraphStarterpack(starter_pack) => &starter_pack.created_at,
+        _ => return None,
+    };
+
+    u64::try_from(created_at.as_ref().timestamp_micros()).ok()
+}
+
+/// Finds the PDS hosting the repository of `user_did` in its DID document.
+async fn resolve_pds(user_did: &str, plc_directory_url: &str) -> anyhow::Result<String> {
+    let url = if let Some(host) = user_did.strip_prefix("did:web:") {
+        format!("https://{}/.well-known/did.json", host)
+    } else if user_did.starts_with("did:plc:") {
+        format!("{}/{}", plc_directory_url.trim_end_matches('/'), user_did)
+    } else {
+        anyhow::bail!("unsupported DID method");
+    };
+
+    let document = reqwest::get(&url).await?.error_for_status()?.text().await?;
+    let document: serde_json::Value = serde_json::from_str(&document)?;
+
+    document["service"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|service| {
+            service["id"]
+                .as_str()
+                .is_some_and(|id| id.ends_with("#atproto_pds"))
+        })
+        .and_then(|service| service["serviceEndpoint"].as_str())
+        .map(str::to_string)
+        .context("the DID document lists no PDS")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::received::{credit_received, received_engagements};
+    use crate::repositories::profile_repository::FixtureProfileRepository;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    const ALICE: &str = "did:plc:alicefixture00000000000";
+
+    /// A repository served from memory, keyed by collection.
+    #[derive(Default)]
+    struct StaticRecordSource {
+        records: HashMap<String, Vec<(String, Value)>>,
+    }
+
+    impl StaticRecordSource {
+        fn with(mut self, collection: AppBskyEventRecord, rkey: &str, record: Value) -> Self {
+            self.records
+                .entry(collection.to_string())
+                .or_default()
+                .push((rkey.to_string(), record));
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl RecordSource for StaticRecordSource {
+        async fn list_records(
+            &self,
+            _user_did: &str,
+            collection: &str,
+        ) -> anyhow::Result<Vec<(String, KnownRecord)>> {
+            let records = self.records.get(collection).cloned().unwrap_or_default();
+
+            records
+                .into_iter()
+                .map(|(rkey, record)| Ok((rkey, serde_json::from_value(record)?)))
+                .collect()
+        }
+    }
+
+    fn subject() -> Value {
+        json!({
+            "uri": "at://did:plc:author/app.bsky.feed.post/3lfa4ptq3lk2c",
+            "cid": "bafyreig2fjxi3rptqdgylg7e5hmjl6mcke7rn2b6cugzlqq3i4zu6rq52q",
+        })
+    }
+
+    fn history() -> StaticRecordSource {
+        StaticRecordSource::default()
+            .with(
+                AppBskyEventRecord::Post,
+                "post1",
+                json!({
+                    "$type": "app.bsky.feed.post",
+                    "text": "hello world",
+                    "createdAt": "2024-01-01T00:00:00.000Z",
+                }),
+            )
+            .with(
+                AppBskyEventRecord::Post,
+                "post2",
+                json!({
+                    "$type": "app.bsky.feed.post",
+                    "text": "look at this",
+                    "createdAt": "2024-01-03T00:00:00.000Z",
+                    "embed": {
+                        "$type": "app.bsky.embed.images",
+                        "images": [{
+                            "alt": "a cat on a keyboard",
+                            "image": {
+                                "$type": "blob",
+                                "ref": { "$link": "bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy" },
+                                "mimeType": "image/jpeg",
+                                "size": 1234,
+                            },
+                        }],
+                    },
+                }),
+            )
+            .with(
+                AppBskyEventRecord::Like,
+                "like1",
+                json!({
+                    "$type": "app.bsky.feed.like",
+                    "subject": subject(),
+                    "createdAt": "2024-01-02T00:00:00.000Z",
+                }),
+            )
+            .with(
+                AppBskyEventRecord::Repost,
+                "repost1",
+                json!({
+                    "$type": "app.bsky.feed.repost",
+                    "subject": subject(),
+                    "createdAt": "2024-01-04T00:00:00.000Z",
+                }),
+            )
+    }
+
+    fn repository() -> Arc<DatabaseRepository> {
+        let mut repository = DatabaseRepository::in_memory();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles");
+        repository.profiles = Arc::new(FixtureProfileRepository::load(&fixtures).unwrap());
+        Arc::new(repository)
+    }
+
+    async fn experience(repository: &Arc<DatabaseRepository>) -> i64 {
+        repository
+            .experience
+            .find_character_experience_by_partition_key(ALICE.to_string())
+            .await
+            .unwrap()
+            .current_experience
+            .0
+    }
+
+    #[tokio::test]
+    async fn history_is_scored_oldest_first() {
+        let repository = repository();
+        let rules = RulesStore::load(None).unwrap();
+
+        let summary = backfill_character(&repository, &rules, ALICE.to_string(), &history())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            BackfillSummary {
+                records: 4,
+                new_events: 4,
+                experience: 230,
+            }
+        );
+        assert_eq!(experience(&repository).await, 230);
+
+        let character = repository
+            .character
+            .find_by_partition_key(ALICE.to_string())
+            .await
+            .unwrap();
+        assert_eq!(character.name, "alice.test");
+        assert_eq!(character.leveling_state.experience, 230);
+        assert!(!character.is_bootstrap_pending());
+
+        let image_post = repository
+            .event
+            .find_event_record(
+                ALICE.to_string(),
+                AppBskyEventRecord::Post.to_string(),
+                "post2".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(image_post.experience_gained, 180);
+    }
+
+    #[tokio::test]
+    async fn rerunning_changes_nothing() {
+        let repository = repository();
+        let rules = RulesStore::load(None).unwrap();
+        backfill_character(&repository, &rules, ALICE.to_string(), &history())
+            .await
+            .unwrap();
+
+        let summary = backfill_character(&repository, &rules, ALICE.to_string(), &history())
+            .await
+            .unwrap();
+
+        assert_eq!(summary.new_events, 0);
+        assert_eq!(experience(&repository).await, 230);
+    }
+
+    #[tokio::test]
+    async fn existing_counter_is_set_to_the_history() {
+        let repository = repository();
+        let rules = RulesStore::load(None).unwrap();
+        repository
+            .experience
+            .increment_character_experience(
+                CharacterExperience {
+                    user_did: ALICE.to_string(),
+                    current_experience: Counter(0),
+                },
+                1260,
+            )
+            .await;
+
+        backfill_character(&repository, &rules, ALICE.to_string(), &history())
+            .await
+            .unwrap();
+
+        assert_eq!(experience(&repository).await, 230);
+    }
+
+    #[tokio::test]
+    async fn received_engagement_is_kept() {
+        let repository = repository();
+        let rules = RulesStore::load(None).unwrap();
+        let like = NewEventDTO::from_record(
+            "did:plc:fan".to_string(),
+            &AppBskyEventRecord::Like.to_string(),
+            "like1".to_string(),
+            &serde_json::from_value(json!({
+                "$type": "app.bsky.feed.like",
+                "subject": {
+                    "uri": format!("at://{}/app.bsky.feed.post/post1", ALICE),
+                    "cid": "bafyreig2fjxi3rptqdgylg7e5hmjl6mcke7rn2b6cugzlqq3i4zu6rq52q",
+                },
+                "createdAt": "2024-01-05T00:00:00.000Z",
+            }))
+            .unwrap(),
+            0,
+        );
+        for engagement in received_engagements(&like) {
+            credit_received(&repository, &rules.current(), &like, &engagement).await;
+        }
+
+        let summary = backfill_character(&repository, &rules, ALICE.to_string(), &history())
+            .await
+            .unwrap();
+
+        assert_eq!(summary.experience, 235);
+        assert_eq!(experience(&repository).await, 235);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_run_alongside_ingestion() {
+        let repository = repository();
+        let timeout = Duration::from_secs(30);
+        assert!(ensure_ingestion_stopped(&repository, timeout).await.is_ok());
+
+        repository.cursor.save_cursor("default".to_string(), 1).await;
+
+        let error = ensure_ingestion_stopped(&repository, timeout).await.unwrap_err();
+        assert!(error.to_string().starts_with("ingestion instance default saved its cursor"));
+        // a checkpoint older than the timeout
+        assert!(ensure_ingestion_stopped(&repository, Duration::ZERO).await.is_ok());
+    }
+}
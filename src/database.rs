@@ -0,0 +1,182 @@
+use crate::args::DatabaseSettings;
+use anyhow::Context;
+use openssl::ssl::{SslContext, SslContextBuilder, SslMethod, SslVerifyMode};
+use scylla::load_balancing::DefaultPolicy;
+use scylla::{ExecutionProfile, Session, SessionBuilder};
+use std::time::Duration;
+
+/// Connects to the cluster described by `settings` and switches to its keyspace.
+pub async fn start_scylla_session(settings: &DatabaseSettings) -> anyhow::Result<Session> {
+    let session = connect(settings).await?;
+
+    session
+        .use_keyspace(&settings.scylla_keyspace, false)
+        .await
+        .with_context(|| {
+            format!(
+                "keyspace {} is not available, create it with the `migrate` command",
+                settings.scylla_keyspace
+            )
+        })?;
+
+    Ok(session)
+}
+
+/// Connects to the cluster without selecting a keyspace.
+pub async fn connect(settings: &DatabaseSettings) -> anyhow::Result<Session> {
+    session_builder(settings)?.build().await.with_context(|| {
+        format!(
+            "failed to connect to ScyllaDB at {}",
+            settings.scylla_nodes.join(", ")
+        )
+    })
+}
+
+/// The session configuration of `settings`: load balancing, consistency, timeouts,
+/// authentication and TLS.
+fn session_builder(settings: &DatabaseSettings) -> anyhow::Result<SessionBuilder> {
+    let mut policy = DefaultPolicy::builder().token_aware(true);
+    if let Some(datacenter) = &settings.scylla_datacenter {
+        policy = policy.prefer_datacenter(datacenter.clone()).permit_dc_failover(true);
+    }
+
+    // statements without an explicit consistency are reads
+    let profile = ExecutionProfile::builder()
+        .load_balancing_policy(policy.build())
+        .consistency(settings.scylla_read_consistency.into())
+        .request_timeout(Some(Duration::from_millis(settings.scylla_request_timeout_ms)))
+        .build();
+
+    let mut builder = SessionBuilder::new()
+        .known_nodes(&settings.scylla_nodes)
+        .connection_timeout(Duration::from_millis(settings.scylla_connection_timeout_ms))
+        .default_execution_profile_handle(profile.into_handle());
+
+    if let Some(username) = &settings.scylla_username {
+        builder = builder.user(username, settings.scylla_password.clone().unwrap_or_default());
+    }
+
+    if settings.scylla_tls {
+        builder = builder.ssl_context(Some(ssl_context(settings)?));
+    }
+
+    Ok(builder)
+}
+
+fn ssl_context(settings: &DatabaseSettings) -> anyhow::Result<SslContext> {
+    let mut context = SslContextBuilder::new(SslMethod::tls())?;
+
+    match &settings.scylla_tls_ca_file {
+        Some(ca_file) => context
+            .set_ca_file(ca_file)
+            .with_context(|| format!("failed to load CA certificate {}", ca_file.display()))?,
+        None => context.set_default_verify_paths()?,
+    }
+    context.set_verify(SslVerifyMode::PEER);
+
+    Ok(context.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::ConsistencyLevel;
+    use scylla::statement::Consistency;
+    use scylla::transport::KnownNode;
+    use std::path::PathBuf;
+
+    fn settings() -> DatabaseSettings {
+        DatabaseSettings {
+            scylla_nodes: vec!["localhost:19042".to_string(), "localhost:19043".to_string()],
+            scylla_keyspace: "bsky_rpg".to_string(),
+            scylla_datacenter: None,
+            scylla_username: None,
+            scylla_password: None,
+            scylla_tls: false,
+            scylla_tls_ca_file: None,
+            scylla_connection_timeout_ms: 5000,
+            scylla_request_timeout_ms: 10000,
+            scylla_read_consistency: ConsistencyLevel::LocalQuorum,
+            scylla_write_consistency: ConsistencyLevel::LocalQuorum,
+            scylla_counter_consistency: ConsistencyLevel::LocalQuorum,
+            scylla_cache_size: 50,
+        }
+    }
+
+    fn load_balancing(builder: &SessionBuilder) -> String {
+        let profile = builder.config.default_execution_profile_handle.to_profile();
+        format!("{:?}", profile.get_load_balancing_policy())
+    }
+
+    #[test]
+    fn defaults_connect_in_plain_text_without_credentials() {
+        let builder = session_builder(&settings()).unwrap();
+        let config = &builder.config;
+
+        assert_eq!(
+            config.known_nodes,
+            vec![
+                KnownNode::Hostname("localhost:19042".to_string()),
+                KnownNode::Hostname("localhost:19043".to_string()),
+            ]
+        );
+        assert_eq!(config.connect_timeout, Duration::from_millis(5000));
+        assert!(config.authenticator.is_none());
+        assert!(config.ssl_context.is_none());
+
+        let profile = config.default_execution_profile_handle.to_profile();
+        assert_eq!(profile.get_consistency(), Consistency::LocalQuorum);
+        assert_eq!(profile.get_request_timeout(), Some(Duration::from_millis(10000)));
+
+        let policy = load_balancing(&builder);
+        assert!(policy.contains("preferences: Any"), "{policy}");
+        assert!(policy.contains("is_token_aware: true"), "{policy}");
+    }
+
+    #[test]
+    fn a_datacenter_is_preferred_with_failover() {
+        let builder = session_builder(&DatabaseSettings {
+            scylla_datacenter: Some("eu-west".to_string()),
+            ..settings()
+        })
+        .unwrap();
+
+        let policy = load_balancing(&builder);
+        assert!(policy.contains(r#"Datacenter("eu-west")"#), "{policy}");
+        assert!(policy.contains("is_token_aware: true"), "{policy}");
+        assert!(policy.contains("permit_dc_failover: true"), "{policy}");
+    }
+
+    #[test]
+    fn a_username_enables_password_authentication() {
+        let builder = session_builder(&DatabaseSettings {
+            scylla_username: Some("cassandra".to_string()),
+            scylla_password: Some("secret".to_string()),
+            ..settings()
+        })
+        .unwrap();
+
+        assert!(builder.config.authenticator.is_some());
+    }
+
+    #[test]
+    fn tls_verifies_the_nodes_with_the_configured_ca() {
+        let builder = session_builder(&DatabaseSettings {
+            scylla_tls: true,
+            ..settings()
+        })
+        .unwrap();
+        let context = builder.config.ssl_context.unwrap();
+        assert_eq!(context.verify_mode(), SslVerifyMode::PEER);
+
+        let missing_ca = PathBuf::from("/nonexistent/ca.pem");
+        let Err(error) = session_builder(&DatabaseSettings {
+            scylla_tls: true,
+            scylla_tls_ca_file: Some(missing_ca),
+            ..settings()
+        }) else {
+            panic!("a missing CA certificate is accepted");
+        };
+        assert!(error.to_string().contains("failed to load CA certificate /nonexistent/ca.pem"));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+/// Shortest alt text (in characters) that can describe anything.
+pub const MIN_ALT_TEXT_LENGTH: usize = 5;
+
+/// Alt texts that only say *that* there is an image, not what it shows.
+const PLACEHOLDERS: [&str; 14] = [
+    "alt", "gif", "graphic", "image", "img", "media", "n/a", "none", "photo", "pic", "picture",
+    "screenshot", "untitled", "video",
+];
+
+/// Extensions of files whose name ends up as alt text.
+const FILE_EXTENSIONS: [&str; 10] = [
+    "jpg", "jpeg", "png", "gif", "webp", "heic", "avif", "bmp", "mp4", "mov",
+];
+
+/// How well one image or video is described for screen readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AltText {
+    Described,
+    Missing,
+    TooShort,
+    Placeholder,
+    FileName,
+}
+
+impl AltText {
+    pub fn classify(alt: &str) -> Self {
+        let alt = alt.trim();
+        let lowercase = alt.to_lowercase();
+
+        if alt.is_empty() {
+            AltText::Missing
+        } else if PLACEHOLDERS.contains(&lowercase.as_str()) {
+            AltText::Placeholder
+        } else if is_file_name(&lowercase) {
+            AltText::FileName
+        } else if alt.chars().count() < MIN_ALT_TEXT_LENGTH {
+            AltText::TooShort
+        } else {
+            AltText::Described
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AltText::Described => "described",
+            AltText::Missing => "missing",
+            AltText::TooShort => "too_short",
+            AltText::Placeholder => "placeholder",
+            AltText::FileName => "file_name",
+        }
+    }
+}
+
+/// `IMG_1234.jpg`, `photo.png`: a single word ending in a media file extension.
+fn is_file_name(alt: &str) -> bool {
+    !alt.contains(char::is_whitespace)
+        && alt
+            .rsplit_once('.')
+            .is_some_and(|(name, extension)| !name.is_empty() && FILE_EXTENSIONS.contains(&extension))
+}
+
+/// Alt text of every image and video attached to a post, in order.
+#[derive(Debug, Clone, Default)]
+pub struct AccessibilityContext {
+    pub media: Vec<AltText>,
+}
+
+impl AccessibilityContext {
+    pub fn from_alt_texts<'a>(alt_texts: impl IntoIterator<Item = &'a str>) -> Self {
+        AccessibilityContext {
+            media: alt_texts.into_iter().map(AltText::classify).collect(),
+        }
+    }
+
+    pub fn described(&self) -> usize {
+        self.media
+            .iter()
+            .filter(|alt_text| **alt_text == AltText::Described)
+            .count()
+    }
+
+    /// Share of the media that is described, from 0 to 1; 0 without media.
+    pub fn coverage(&self) -> f32 {
+        if self.media.is_empty() {
+            return 0.0;
+        }
+
+        self.described() as f32 / self.media.len() as f32
+    }
+
+    /// Whether the post has media and all of it is described.
+    pub fn fully_described(&self) -> bool {
+        !self.media.is_empty() && self.described() == self.media.len()
+    }
+
+    /// The accessibility part of the CQL `event_data` map.
+    pub fn to_event_data(&self) -> HashMap<String, String> {
+        let alt_texts: Vec<_> = self.media.iter().map(AltText::as_str).collect();
+
+        HashMap::from([
+            ("media_count".to_string(), self.media.len().to_string()),
+            ("described_media_count".to_string(), self.described().to_string()),
+            ("alt_text_coverage".to_string(), format!("{:.2}", self.coverage())),
+            ("alt_texts".to_string(), alt_texts.join(",")),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alt_texts_are_classified() {
+        assert_eq!(AltText::classify("A cat asleep on a keyboard"), AltText::Described);
+        assert_eq!(AltText::classify("   "), AltText::Missing);
+        assert_eq!(AltText::classify("IMG"), AltText::Placeholder);
+        assert_eq!(AltText::classify("Screenshot"), AltText::Placeholder);
+        assert_eq!(AltText::classify("IMG_1234.JPG"), AltText::FileName);
+        assert_eq!(AltText::classify("cat"), AltText::TooShort);
+    }
+
+    #[test]
+    fn one_described_image_out_of_four_is_a_quarter() {
+        let accessibility =
+            AccessibilityContext::from_alt_texts(["A cat asleep on a keyboard", "img", "", "dsc.png"]);
+
+        assert_eq!(accessibility.described(), 1);
+        assert_eq!(accessibility.coverage(), 0.25);
+        assert!(!accessibility.fully_described());
+        assert_eq!(
+            accessibility.to_event_data()["alt_texts"],
+            "described,placeholder,missing,file_name"
+        );
+    }
+
+    #[test]
+    fn posts_without_media_are_not_described() {
+        let accessibility = AccessibilityContext::default();
+
+        assert_eq!(accessibility.coverage(), 0.0);
+        assert!(!accessibility.fully_described());
+    }
+}
@@ -0,0 +1,433 @@
+use crate::events::accessibility::AccessibilityContext;
+use crate::events::facets::FacetsContext;
+use atrium_api::app::bsky::embed::record_with_media::MainMediaRefs;
+use atrium_api::app::bsky::embed::{external, images, video};
+use atrium_api::app::bsky::feed::post::{self, RecordEmbedRefs};
+use atrium_api::com::atproto::repo::strong_ref;
+use atrium_api::record::KnownRecord;
+use atrium_api::types::Union::Refs;
+use charybdis::types::Timestamp;
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Structured data of an event, as seen by the handlers.
+///
+/// The stringly-typed `event_data` map stored in ScyllaDB is only derived from it
+/// at persistence time, see [`EventContext::to_event_data`].
+#[derive(Debug, Clone)]
+pub enum EventContext {
+    Post(Box<PostContext>),
+    Like(SubjectContext),
+    Repost(SubjectContext),
+    Follow(ActorContext),
+    Block(ActorContext),
+    ListItem(ListItemContext),
+    StarterPack(StarterPackContext),
+    /// Engagement of another account with one of the user's records.
+    Received(ReceivedContext),
+    /// Audit of a deleted record whose experience was revoked.
+    Revocation {
+        revoked_experience: i64,
+        original_event_at: Timestamp,
+    },
+    /// Audit of an edited record that was re-scored.
+    Rescore {
+        context: Box<EventContext>,
+        experience_delta: i64,
+    },
+    /// A record from a collection we don't score.
+    Unsupported,
+}
+
+#[derive(Debug, Clone)]
+pub struct PostContext {
+    pub text: String,
+    /// Length of the text in graphemes, the way Bluesky counts its 300 character limit.
+    pub length: usize,
+    pub has_image: bool,
+    /// Alt text of the attached images and video.
+    pub accessibility: AccessibilityContext,
+    pub langs: Vec<String>,
+    pub has_video: bool,
+    /// The thread this post replies to, `None` for top-level posts.
+    pub reply: Option<ReplyContext>,
+    /// The post quoted, if this is a quote.
+    pub quote_uri: Option<String>,
+    /// The page shared as a link card.
+    pub link_uri: Option<String>,
+    /// Hashtags, mentions and links in the text.
+    pub facets: FacetsContext,
+}
+
+/// Where a reply sits in its thread.
+#[derive(Debug, Clone)]
+pub struct ReplyContext {
+    pub root_uri: String,
+    pub root_did: Option<String>,
+    pub parent_uri: String,
+    pub parent_did: Option<String>,
+}
+
+/// The record a like or repost points at.
+#[derive(Debug, Clone)]
+pub struct SubjectContext {
+    pub uri: String,
+    pub cid: String,
+}
+
+/// The account a follow or block points at.
+#[derive(Debug, Clone)]
+pub struct ActorContext {
+    pub subject_did: String,
+}
+
+/// An account added to a list.
+#[derive(Debug, Clone)]
+pub struct ListItemContext {
+    pub list_uri: String,
+    pub subject_did: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct StarterPackContext {
+    pub name: String,
+    pub list_uri: String,
+    pub feed_count: usize,
+}
+
+/// Who engaged with which of the user's records.
+#[derive(Debug, Clone)]
+pub struct ReceivedContext {
+    pub actor_did: String,
+    pub subject_uri: String,
+}
+
+impl EventContext {
+    pub fn from_record(record: &KnownRecord) -> Self {
+        match record {
+            KnownRecord::AppBskyFeedPost(post) => EventContext::Post(Box::new(PostContext::from(&**post))),
+            KnownRecord::AppBskyFeedLike(like) => {
+                EventContext::Like(SubjectContext::from(&like.subject))
+            }
+            KnownRecord::AppBskyFeedRepost(repost) => {
+                EventContext::Repost(SubjectContext::from(&repost.subject))
+            }
+            KnownRecord::AppBskyGraphFollow(follow) => EventContext::Follow(ActorContext {
+                subject_did: follow.subject.to_string(),
+            }),
+            KnownRecord::AppBskyGraphBlock(block) => EventContext::Block(ActorContext {
+                subject_did: block.subject.to_string(),
+            }),
+            KnownRecord::AppBskyGraphListitem(list_item) => {
+                EventContext::ListItem(ListItemContext {
+                    list_uri: list_item.list.clone(),
+                    subject_did: list_item.subject.to_string(),
+                })
+            }
+            KnownRecord::AppBskyGraphStarterpack(starter_pack) => {
+                EventContext::StarterPack(StarterPackContext {
+                    name: starter_pack.name.clone(),
+                    list_uri: starter_pack.list.clone(),
+                    feed_count: starter_pack.feeds.as_ref().map_or(0, Vec::len),
+                })
+            }
+            _ => EventContext::Unsupported,
+        }
+    }
+
+    /// Flattens the context into the CQL `event_data` map.
+    pub fn to_event_data(&self) -> HashMap<String, String> {
+        match self {
+            EventContext::Post(post) => {
+                let mut event_data = HashMap::from([
+                    ("text".to_string(), post.text.clone()),
+                    ("length".to_string(), post.length.to_string()),
+                    ("has_image".to_string(), post.has_image.to_string()),
+                    // kept for existing readers: every image and video is described
+                    (
+                        "image_has_alt_text".to_string(),
+                        post.accessibility.fully_described().to_string(),
+                    ),
+                    ("has_video".to_string(), post.has_video.to_string()),
+                    ("langs".to_string(), post.langs.join(",")),
+                    ("is_reply".to_string(), post.reply.is_some().to_string()),
+                    ("is_quote".to_string(), post.quote_uri.is_some().to_string()),
+                ]);
+                if let Some(reply) = &post.reply {
+                    event_data.insert("reply_root_uri".to_string(), reply.root_uri.clone());
+                    event_data.insert("reply_parent_uri".to_string(), reply.parent_uri.clone());
+                    if let Some(did) = &reply.root_did {
+                        event_data.insert("reply_root_did".to_string(), did.clone());
+                    }
+                    if let Some(did) = &reply.parent_did {
+                        event_data.insert("reply_parent_did".to_string(), did.clone());
+                    }
+                }
+                if let Some(uri) = &post.quote_uri {
+                    event_data.insert("quote_uri".to_string(), uri.clone());
+                }
+                if let Some(uri) = &post.link_uri {
+                    event_data.insert("link_uri".to_string(), uri.clone());
+                }
+                event_data.extend(post.accessibility.to_event_data());
+                event_data.extend(post.facets.to_event_data());
+                event_data
+            }
+            EventContext::Like(subject) | EventContext::Repost(subject) => HashMap::from([
+                ("subject_uri".to_string(), subject.uri.clone()),
+                ("subject_cid".to_string(), subject.cid.clone()),
+            ]),
+            EventContext::Follow(actor) | EventContext::Block(actor) => {
+                HashMap::from([("subject_did".to_string(), actor.subject_did.clone())])
+            }
+            EventContext::ListItem(list_item) => HashMap::from([
+                ("list_uri".to_string(), list_item.list_uri.clone()),
+                ("subject_did".to_string(), list_item.subject_did.clone()),
+            ]),
+            EventContext::StarterPack(starter_pack) => HashMap::from([
+                ("name".to_string(), starter_pack.name.clone()),
+                ("list_uri".to_string(), starter_pack.list_uri.clone()),
+                ("feed_count".to_string(), starter_pack.feed_count.to_string()),
+            ]),
+            EventContext::Received(received) => HashMap::from([
+                ("actor_did".to_string(), received.actor_did.clone()),
+                ("subject_uri".to_string(), received.subject_uri.clone()),
+            ]),
+            EventContext::Revocation {
+                revoked_experience,
+                original_event_at,
+            } => HashMap::from([
+                (
+                    "revoked_experience".to_string(),
+                    revoked_experience.to_string(),
+                ),
+                (
+                    "original_event_at".to_string(),
+                    original_event_at.to_rfc3339(),
+                ),
+            ]),
+            EventContext::Rescore {
+                context,
+                experience_delta,
+            } => {
+                let mut event_data = context.to_event_data();
+                event_data.insert("experience_delta".to_string(), experience_delta.to_string());
+                event_data
+            }
+            EventContext::Unsupported => HashMap::new(),
+        }
+    }
+}
+
+/// Media attached to a post, directly or next to a quoted record.
+enum Media<'a> {
+    Images(&'a images::Main),
+    Video(&'a video::Main),
+    External(&'a external::Main),
+}
+
+impl From<&post::Record> for PostContext {
+    fn from(post: &post::Record) -> Self {
+        let (media, quote_uri) = match &post.embed {
+            Some(Refs(RecordEmbedRefs::AppBskyEmbedImagesMain(images))) => {
+                (Some(Media::Images(images)), None)
+            }
+            Some(Refs(RecordEmbedRefs::AppBskyEmbedVideoMain(video))) => {
+                (Some(Media::Video(video)), None)
+            }
+            Some(Refs(RecordEmbedRefs::AppBskyEmbedExternalMain(external))) => {
+                (Some(Media::External(external)), None)
+            }
+            Some(Refs(RecordEmbedRefs::AppBskyEmbedRecordMain(quote))) => {
+                (None, Some(quote.record.uri.clone()))
+            }
+            Some(Refs(RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(quote))) => {
+                let media = match &quote.media {
+                    Refs(MainMediaRefs::AppBskyEmbedImagesMain(images)) => Some(Media::Images(images)),
+                    Refs(MainMediaRefs::AppBskyEmbedVideoMain(video)) => Some(Media::Video(video)),
+                    Refs(MainMediaRefs::AppBskyEmbedExternalMain(external)) => {
+                        Some(Media::External(external))
+                    }
+                    _ => None,
+                };
+
+                (media, Some(quote.record.record.uri.clone()))
+            }
+            _ => (None, None),
+        };
+
+        let accessibility = match &media {
+            Some(Media::Images(images)) => {
+                AccessibilityContext::from_alt_texts(images.images.iter().map(|image| image.alt.as_str()))
+            }
+            Some(Media::Video(video)) => {
+                AccessibilityContext::from_alt_texts([video.alt.as_deref().unwrap_or_default()])
+            }
+            _ => AccessibilityContext::default(),
+        };
+
+        let link_uri = match &media {
+            Some(Media::External(external)) => Some(external.external.uri.clone()),
+            _ => None,
+        };
+
+        let reply = post.reply.as_ref().map(|reply| ReplyContext {
+            root_uri: reply.root.uri.clone(),
+            root_did: repository_did(&reply.root.uri),
+            parent_uri: reply.parent.uri.clone(),
+            parent_did: repository_did(&reply.parent.uri),
+        });
+
+        let langs = post
+            .langs
+            .iter()
+            .flatten()
+            .map(|lang| lang.as_ref().as_str().to_string())
+            .collect();
+
+        PostContext {
+            text: post.text.clone(),
+            length: post.text.graphemes(true).count(),
+            has_image: matches!(media, Some(Media::Images(_))),
+            accessibility,
+            has_video: matches!(media, Some(Media::Video(_))),
+            langs,
+            reply,
+            quote_uri,
+            link_uri,
+            facets: FacetsContext::from_facets(post.facets.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+/// The DID of the repository holding the record at `uri`; `None` for handle-based URIs.
+pub(crate) fn repository_did(uri: &str) -> Option<String> {
+    let authority = uri.strip_prefix("at://")?.split('/').next()?;
+
+    authority.starts_with("did:").then(|| authority.to_string())
+}
+
+impl From<&strong_ref::Main> for SubjectContext {
+    fn from(subject: &strong_ref::Main) -> Self {
+        SubjectContext {
+            uri: subject.uri.clone(),
+            cid: subject.cid.as_ref().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const AUTHOR_POST: &str = "at://did:plc:author/app.bsky.feed.post/3lfa4ptq3lk2c";
+    const ROOT_POST: &str = "at://did:plc:root/app.bsky.feed.post/3lfa4ptq3lk2b";
+
+    fn post_context(record: Value) -> PostContext {
+        let mut record = record;
+        record["$type"] = json!("app.bsky.feed.post");
+        record["createdAt"] = json!("2025-01-01T00:00:00.000Z");
+
+        match EventContext::from_record(&serde_json::from_value(record).unwrap()) {
+            EventContext::Post(post) => *post,
+            context => panic!("expected a post, got {:?}", context),
+        }
+    }
+
+    fn strong_ref(uri: &str) -> Value {
+        json!({ "uri": uri, "cid": "bafyreig2fjxi3rptqdgylg7e5hmjl6mcke7rn2b6cugzlqq3i4zu6rq52q" })
+    }
+
+    fn blob() -> Value {
+        json!({
+            "$type": "blob",
+            "ref": { "$link": "bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy" },
+            "mimeType": "video/mp4",
+            "size": 1234,
+        })
+    }
+
+    #[test]
+    fn top_level_posts_have_no_thread() {
+        let post = post_context(json!({ "text": "hello world" }));
+
+        assert!(post.reply.is_none());
+        assert!(post.quote_uri.is_none());
+        assert!(post.link_uri.is_none());
+        assert!(!post.has_image && !post.has_video);
+    }
+
+    #[test]
+    fn replies_know_their_root_and_parent_authors() {
+        let post = post_context(json!({
+            "text": "I agree",
+            "reply": { "root": strong_ref(ROOT_POST), "parent": strong_ref(AUTHOR_POST) },
+        }));
+
+        let reply = post.reply.unwrap();
+        assert_eq!(reply.root_uri, ROOT_POST);
+        assert_eq!(reply.root_did.as_deref(), Some("did:plc:root"));
+        assert_eq!(reply.parent_uri, AUTHOR_POST);
+        assert_eq!(reply.parent_did.as_deref(), Some("did:plc:author"));
+    }
+
+    #[test]
+    fn quotes_keep_the_media_next_to_the_quoted_post() {
+        let post = post_context(json!({
+            "text": "look at this",
+            "embed": {
+                "$type": "app.bsky.embed.recordWithMedia",
+                "record": { "record": strong_ref(AUTHOR_POST) },
+                "media": { "$type": "app.bsky.embed.video", "video": blob() },
+            },
+        }));
+
+        assert_eq!(post.quote_uri.as_deref(), Some(AUTHOR_POST));
+        assert!(post.has_video);
+    }
+
+    #[test]
+    fn link_cards_are_link_shares() {
+        let post = post_context(json!({
+            "text": "good read",
+            "embed": {
+                "$type": "app.bsky.embed.external",
+                "external": { "uri": "https://example.com/article", "title": "Article", "description": "" },
+            },
+        }));
+
+        assert_eq!(post.link_uri.as_deref(), Some("https://example.com/article"));
+        assert!(post.quote_uri.is_none());
+    }
+
+    #[test]
+    fn length_counts_graphemes_not_bytes() {
+        let post = post_context(json!({ "text": "café 👩‍👩‍👧" }));
+
+        assert_eq!(post.length, 6);
+    }
+
+    #[test]
+    fn facets_and_languages_are_read_from_the_record() {
+        let post = post_context(json!({
+            "text": "#Rust with @friend.bsky.social",
+            "langs": ["en-US"],
+            "facets": [
+                {
+                    "index": { "byteStart": 0, "byteEnd": 5 },
+                    "features": [{ "$type": "app.bsky.richtext.facet#tag", "tag": "Rust" }],
+                },
+                {
+                    "index": { "byteStart": 11, "byteEnd": 30 },
+                    "features": [{ "$type": "app.bsky.richtext.facet#mention", "did": "did:plc:friend" }],
+                },
+            ],
+        }));
+
+        assert_eq!(post.facets.hashtags, ["rust"]);
+        assert_eq!(post.facets.mentions, ["did:plc:friend"]);
+        assert_eq!(post.langs, ["en-US"]);
+        assert_eq!(EventContext::Post(Box::new(post)).to_event_data()["hashtags"], "rust");
+    }
+}
@@ -1,39 +1,27 @@
 use crate::events::create::CreateEventHandler;
 use crate::events::dto::NewEventDTO;
+use crate::events::AppBskyEventRecord;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
 
-pub struct CreatePostEvent {}
+pub struct CreatePostEvent {
+    rules: Arc<ExperienceRules>,
+}
 
 impl CreatePostEvent {
-    pub fn new() -> Self {
-        CreatePostEvent {}
+    pub fn new(rules: Arc<ExperienceRules>) -> Self {
+        CreatePostEvent { rules }
     }
 }
 
 #[async_trait::async_trait]
 impl CreateEventHandler for CreatePostEvent {
     fn calculate_exp(&self, dto: &NewEventDTO) -> i32 {
-        let mut exp = 30;
-
-        if dto
-            .context
-            .get("has_image")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap()
-        {
-            exp += 100;
-        }
-
-        if dto
-            .context
-            .get("image_has_alt_text")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap()
-        {
-            exp += 50;
-        }
+        self.rules
+            .experience_for(&AppBskyEventRecord::Post.to_string(), &dto.context)
+    }
 
-        exp
+    fn rules(&self) -> &ExperienceRules {
+        &self.rules
     }
 }
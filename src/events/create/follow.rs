@@ -0,0 +1,33 @@
+use crate::events::context::EventContext;
+use crate::events::create::CreateEventHandler;
+use crate::events::dto::NewEventDTO;
+use crate::events::AppBskyEventRecord;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
+
+pub struct FollowEvent {
+    rules: Arc<ExperienceRules>,
+}
+
+impl FollowEvent {
+    pub fn new(rules: Arc<ExperienceRules>) -> Self {
+        FollowEvent { rules }
+    }
+}
+
+#[async_trait::async_trait]
+impl CreateEventHandler for FollowEvent {
+    fn calculate_exp(&self, dto: &NewEventDTO) -> i32 {
+        // following yourself is not a social interaction
+        if matches!(&dto.context, EventContext::Follow(actor) if actor.subject_did == dto.user_did) {
+            return 0;
+        }
+
+        self.rules
+            .experience_for(&AppBskyEventRecord::Follow.to_string(), &dto.context)
+    }
+
+    fn rules(&self) -> &ExperienceRules {
+        &self.rules
+    }
+}
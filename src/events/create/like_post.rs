@@ -1,16 +1,26 @@
 use crate::events::create::CreateEventHandler;
 use crate::events::dto::NewEventDTO;
+use crate::events::AppBskyEventRecord;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
 
-pub struct LikePostEvent {}
+pub struct LikePostEvent {
+    rules: Arc<ExperienceRules>,
+}
 
 impl LikePostEvent {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(rules: Arc<ExperienceRules>) -> Self {
+        Self { rules }
     }
 }
 #[async_trait::async_trait]
 impl CreateEventHandler for LikePostEvent {
-    fn calculate_exp(&self, _: &NewEventDTO) -> i32 {
-        10
+    fn calculate_exp(&self, dto: &NewEventDTO) -> i32 {
+        self.rules
+            .experience_for(&AppBskyEventRecord::Like.to_string(), &dto.context)
+    }
+
+    fn rules(&self) -> &ExperienceRules {
+        &self.rules
     }
 }
@@ -0,0 +1,27 @@
+use crate::events::create::CreateEventHandler;
+use crate::events::dto::NewEventDTO;
+use crate::events::AppBskyEventRecord;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
+
+pub struct ListItemEvent {
+    rules: Arc<ExperienceRules>,
+}
+
+impl ListItemEvent {
+    pub fn new(rules: Arc<ExperienceRules>) -> Self {
+        ListItemEvent { rules }
+    }
+}
+
+#[async_trait::async_trait]
+impl CreateEventHandler for ListItemEvent {
+    fn calculate_exp(&self, dto: &NewEventDTO) -> i32 {
+        self.rules
+            .experience_for(&AppBskyEventRecord::ListItem.to_string(), &dto.context)
+    }
+
+    fn rules(&self) -> &ExperienceRules {
+        &self.rules
+    }
+}
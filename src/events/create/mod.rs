@@ -1,132 +1,250 @@
+use crate::events::create::block::BlockEvent;
 use crate::events::create::create_post::CreatePostEvent;
+use crate::events::create::follow::FollowEvent;
 use crate::events::create::like_post::LikePostEvent;
+use crate::events::create::list_item::ListItemEvent;
 use crate::events::create::repost::RepostEvent;
+use crate::events::create::starter_pack::StarterPackEvent;
 use crate::events::dto::NewEventDTO;
+use crate::events::received::{credit_received, received_engagements};
+use crate::events::user_locks::{acquire_all, UserLocks};
 use crate::events::CreateEventPayload;
 use crate::leveling::{calculate_experience, LevelResponse};
 use crate::models::character::Character;
 use crate::models::character_experience::CharacterExperience;
+use crate::models::pending_bootstrap::PendingBootstrap;
 use crate::repositories::DatabaseRepository;
+use crate::rules::{ExperienceRules, RulesStore};
 use atrium_api::record::KnownRecord;
 use atrium_api::record::KnownRecord::AppBskyFeedPost;
 use charybdis::types::Counter;
-use paris::info;
+use chrono::Utc;
+use paris::{info, warn};
 use std::sync::Arc;
 use tokio::sync::Semaphore;
-use KnownRecord::{AppBskyFeedLike, AppBskyFeedRepost};
+use tokio::task::JoinHandle;
+use KnownRecord::{
+    AppBskyFeedLike, AppBskyFeedRepost, AppBskyGraphBlock, AppBskyGraphFollow,
+    AppBskyGraphListitem, AppBskyGraphStarterpack,
+};
 
+mod block;
 pub mod create_post;
+mod follow;
 mod like_post;
+mod list_item;
 mod repost;
+mod starter_pack;
 
 #[async_trait::async_trait]
-trait CreateEventHandler {
+pub(crate) trait CreateEventHandler {
     async fn handle(
         &mut self,
         repository: &Arc<DatabaseRepository>,
         payload: &NewEventDTO,
-    ) -> LevelResponse {
-        // find all the data we need
-        let character = repository
-            .character
-            .find_by_partition_key(payload.user_did.clone())
-            .await;
-
-        let mut character = match character {
-            Some(character) => character,
-            None => {
-                let response = repository
-                    .bsky
-                    .get_author_profile(payload.user_did.clone())
-                    .await;
-                info!("Creating new character for user {}", payload.user_did);
-                Character::from(response)
-            }
-        };
-
-        let character_experience = repository
-            .character
-            .find_character_experience_by_partition_key(payload.user_did.clone())
-            .await;
-
-        let character_experience = match character_experience {
-            Some(character_experience) => character_experience,
-            None => {
-                let character_experience = CharacterExperience {
-                    user_did: payload.user_did.clone(),
-                    current_experience: Counter(0),
-                };
-
-                repository
-                    .character
-                    .increment_character_experience(
-                        character_experience,
-                        character.leveling_state.experience as i64,
-                    )
-                    .await;
-
-                CharacterExperience {
-                    user_did: payload.user_did.clone(),
-                    current_experience: Counter(character.leveling_state.experience as i64),
-                }
-            }
-        };
-
-        // calculate the experience
-        let current_experience = character_experience.get_experience();
-        let action_gained_experience = self.calculate_exp(payload);
-        let new_experience = current_experience.saturating_add(action_gained_experience);
-        let leveling_response_dto = calculate_experience(current_experience, new_experience);
-
-        repository
-            .character
-            .update_character(&mut character, leveling_response_dto.clone())
-            .await;
-
-        repository
-            .event
-            .insert_event(payload, leveling_response_dto.clone())
-            .await;
-
-        // persist the changes
-        repository
-            .character
-            .increment_character_experience(character_experience, action_gained_experience as i64)
-            .await;
-
-        leveling_response_dto
+    ) -> Option<LevelResponse> {
+        grant_experience(repository, self.rules(), payload, self.calculate_exp(payload)).await
     }
 
     fn calculate_exp(&self, payload: &NewEventDTO) -> i32;
+
+    fn rules(&self) -> &ExperienceRules;
 }
 
-pub async fn create_event_handler(
+/// Grants `experience` for `payload` to its user, bootstrapping their character on their first
+/// event, and persists the event. Returns `None` if the event was already granted: the cursor is
+/// rewound on every (re)connect, so events are delivered at least once.
+pub(crate) async fn grant_experience(
+    repository: &Arc<DatabaseRepository>,
+    rules: &ExperienceRules,
+    payload: &NewEventDTO,
+    experience: i32,
+) -> Option<LevelResponse> {
+    let already_granted = repository
+        .event
+        .find_event_record(
+            payload.user_did.clone(),
+            payload.event_type.clone(),
+            payload.event_id.clone(),
+        )
+        .await
+        .is_some();
+    if already_granted {
+        return None;
+    }
+
+    // find all the data we need
+    let character = repository
+        .character
+        .find_by_partition_key(payload.user_did.clone())
+        .await;
+
+    let mut character = match character {
+        Some(character) => character,
+        None => bootstrap_character(repository, rules, &payload.user_did).await?,
+    };
+
+    let character_experience = repository
+        .experience
+        .find_character_experience_by_partition_key(payload.user_did.clone())
+        .await;
+
+    let character_experience = match character_experience {
+        Some(character_experience) => character_experience,
+        None => {
+            let character_experience = CharacterExperience {
+                user_did: payload.user_did.clone(),
+                current_experience: Counter(0),
+            };
+
+            repository
+                .experience
+                .increment_character_experience(
+                    character_experience,
+                    character.leveling_state.experience,
+                )
+                .await;
+
+            CharacterExperience {
+                user_did: payload.user_did.clone(),
+                current_experience: Counter(character.leveling_state.experience),
+            }
+        }
+    };
+
+    // calculate the experience
+    let current_experience = character_experience.get_experience();
+    let new_experience = current_experience.saturating_add(experience as i64);
+    let leveling_response_dto = calculate_experience(rules.curve(), current_experience, new_experience);
+
+    repository
+        .character
+        .update_character(&mut character, leveling_response_dto.clone())
+        .await;
+
+    repository
+        .event
+        .insert_event(payload, leveling_response_dto.clone())
+        .await;
+
+    repository
+        .experience
+        .increment_character_experience(character_experience, experience as i64)
+        .await;
+
+    // written last: once it exists the event counts as granted, see the check above
+    repository
+        .event
+        .insert_event_record(payload, experience)
+        .await;
+
+    Some(leveling_response_dto)
+}
+
+/// Dispatches a create event without waiting: the spawned task waits for the user's previous
+/// events, then for a worker.
+pub fn create_event_handler(
     repository: &Arc<DatabaseRepository>,
     payload: CreateEventPayload,
+    rules: &RulesStore,
+    locks: &UserLocks,
     semaphore: Arc<Semaphore>,
-) {
+) -> Option<JoinHandle<()>> {
+    let Some(mut handler) = select_event_handler(&payload.commit_data.record, rules) else {
+        info!(
+            "[Ignored][{}] Event of user {} has no handler",
+            payload.commit_data.info.collection.as_str(),
+            payload.event_info.did.as_str()
+        );
+        return None;
+    };
     let event_payload = NewEventDTO::from(&payload);
+    let engagements = received_engagements(&event_payload);
+    let rules = rules.current();
 
     let repo = Arc::clone(repository);
-    let permit = semaphore.acquire_owned().await.unwrap(); // Acquire a semaphore permit
-
-    tokio::spawn(async move {
-        let response = select_event_handler(&payload.commit_data.record)
-            .handle(&repo, &event_payload)
-            .await;
-        info!(
-            "[Created][{}] User {} gained {} experience",
-            event_payload.event_type, event_payload.user_did, response.experience
-        );
+    // Queue behind the previous events of the user and of the recipients
+    let tickets = locks.tickets(
+        std::iter::once(event_payload.user_did.as_str())
+            .chain(engagements.iter().map(|engagement| engagement.recipient_did.as_str())),
+    );
+
+    let task = tokio::spawn(async move {
+        let guards = acquire_all(tickets).await;
+        let permit = semaphore.acquire_owned().await.unwrap(); // Acquire a semaphore permit
+
+        if let Some(response) = handler.handle(&repo, &event_payload).await {
+            info!(
+                "[Created][{}] User {} gained {} experience",
+                event_payload.event_type, event_payload.user_did, response.experience
+            );
+
+            // only events that counted for their author count for the recipients
+            for engagement in &engagements {
+                if let Some(response) = credit_received(&repo, &rules, &event_payload, engagement).await {
+                    info!(
+                        "[Created][{}] User {} gained {} experience",
+                        engagement.event_type, engagement.recipient_did, response.experience
+                    );
+                }
+            }
+        }
         drop(permit); // Release the semaphore permit
+        drop(guards);
     });
+
+    Some(task)
+}
+
+/// Creates the character of a first-seen user from their profile.
+///
+/// When the profile is only temporarily unavailable the character starts without its past
+/// experience and is queued for reconciliation, so the event isn't lost. Returns `None` for
+/// accounts without a profile (deleted, deactivated or suspended): their events are skipped.
+async fn bootstrap_character(
+    repository: &Arc<DatabaseRepository>,
+    rules: &ExperienceRules,
+    user_did: &str,
+) -> Option<Character> {
+    match repository.profiles.get_author_profile(user_did.to_string()).await {
+        Ok(response) => {
+            info!("Creating new character for user {}", user_did);
+            Some(Character::from_profile(response, rules))
+        }
+        Err(e) if e.is_transient() => {
+            warn!("Creating character for user {} without profile ({}), bootstrap pending", user_did, e);
+            repository
+                .character
+                .save_pending_bootstrap(&PendingBootstrap {
+                    user_did: user_did.to_string(),
+                    attempts: 1,
+                    last_error: e.to_string(),
+                    pending_since: Utc::now(),
+                })
+                .await;
+
+            Some(Character::pending(user_did.to_string(), rules))
+        }
+        Err(e) => {
+            warn!("Skipping event of user {}: {}", user_did, e);
+            None
+        }
+    }
 }
 
-fn select_event_handler(record: &KnownRecord) -> Box<dyn CreateEventHandler + Send + Sync> {
+pub(crate) fn select_event_handler(
+    record: &KnownRecord,
+    rules: &RulesStore,
+) -> Option<Box<dyn CreateEventHandler + Send + Sync>> {
     match record {
-        AppBskyFeedPost(_) => Box::new(CreatePostEvent::new()),
-        AppBskyFeedLike(_) => Box::new(LikePostEvent::new()),
-        AppBskyFeedRepost(_) => Box::new(RepostEvent::new()),
-        _ => panic!("Unknown event type"),
+        AppBskyFeedPost(_) => Some(Box::new(CreatePostEvent::new(rules.current()))),
+        AppBskyFeedLike(_) => Some(Box::new(LikePostEvent::new(rules.current()))),
+        AppBskyFeedRepost(_) => Some(Box::new(RepostEvent::new(rules.current()))),
+        AppBskyGraphFollow(_) => Some(Box::new(FollowEvent::new(rules.current()))),
+        AppBskyGraphBlock(_) => Some(Box::new(BlockEvent::new(rules.current()))),
+        AppBskyGraphListitem(_) => Some(Box::new(ListItemEvent::new(rules.current()))),
+        AppBskyGraphStarterpack(_) => Some(Box::new(StarterPackEvent::new(rules.current()))),
+        // everything else is explicitly ignored, see create_event_handler
+        _ => None,
     }
 }
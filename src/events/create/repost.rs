@@ -1,17 +1,27 @@
 use crate::events::create::CreateEventHandler;
 use crate::events::dto::NewEventDTO;
+use crate::events::AppBskyEventRecord;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
 
-pub struct RepostEvent {}
+pub struct RepostEvent {
+    rules: Arc<ExperienceRules>,
+}
 
 impl RepostEvent {
-    pub fn new() -> Self {
-        RepostEvent {}
+    pub fn new(rules: Arc<ExperienceRules>) -> Self {
+        RepostEvent { rules }
     }
 }
 
 #[async_trait::async_trait]
 impl CreateEventHandler for RepostEvent {
-    fn calculate_exp(&self, _: &NewEventDTO) -> i32 {
-        10
+    fn calculate_exp(&self, dto: &NewEventDTO) -> i32 {
+        self.rules
+            .experience_for(&AppBskyEventRecord::Repost.to_string(), &dto.context)
+    }
+
+    fn rules(&self) -> &ExperienceRules {
+        &self.rules
     }
 }
@@ -0,0 +1,27 @@
+use crate::events::create::CreateEventHandler;
+use crate::events::dto::NewEventDTO;
+use crate::events::AppBskyEventRecord;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
+
+pub struct StarterPackEvent {
+    rules: Arc<ExperienceRules>,
+}
+
+impl StarterPackEvent {
+    pub fn new(rules: Arc<ExperienceRules>) -> Self {
+        StarterPackEvent { rules }
+    }
+}
+
+#[async_trait::async_trait]
+impl CreateEventHandler for StarterPackEvent {
+    fn calculate_exp(&self, dto: &NewEventDTO) -> i32 {
+        self.rules
+            .experience_for(&AppBskyEventRecord::StarterPack.to_string(), &dto.context)
+    }
+
+    fn rules(&self) -> &ExperienceRules {
+        &self.rules
+    }
+}
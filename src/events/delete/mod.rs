@@ -1,6 +1,143 @@
+use crate::events::context::EventContext;
+use crate::events::dto::NewEventDTO;
+use crate::events::received::record_uri;
+use crate::events::user_locks::{acquire_all, UserLocks};
+use crate::events::DeleteEventPayload;
+use crate::leveling::calculate_experience;
+use crate::models::event_record::EventRecord;
 use crate::repositories::DatabaseRepository;
-use jetstream_oxide::events::commit::CommitInfo;
-use jetstream_oxide::events::EventInfo;
+use crate::rules::{ExperienceRules, RulesStore};
+use paris::info;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
 
-pub async fn _delete_event_handler(_: &Arc<DatabaseRepository>, _: EventInfo, _: CommitInfo) {}
+/// Dispatches a delete event without waiting, like [`crate::events::create::create_event_handler`].
+pub fn delete_event_handler(
+    repository: &Arc<DatabaseRepository>,
+    payload: DeleteEventPayload,
+    rules: &RulesStore,
+    locks: &Arc<UserLocks>,
+    semaphore: Arc<Semaphore>,
+) -> JoinHandle<()> {
+    let rules = rules.current();
+    let repo = Arc::clone(repository);
+    let locks = Arc::clone(locks);
+    let user_did = payload.event_info.did.as_str().to_string();
+    let collection = payload.commit_info.collection.as_str().to_string();
+    let ticket = locks.ticket(&user_did); // Queue behind the user's previous events
+
+    tokio::spawn(async move {
+        let guard = ticket.acquire().await;
+        let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap(); // Acquire a semaphore permit
+        let deleted_at = payload.event_info.time_us;
+
+        // read after the user's previous events were applied, so a record created just before is found
+        let record = repo
+            .event
+            .find_event_record(
+                user_did.clone(),
+                collection.clone(),
+                payload.commit_info.rkey.clone(),
+            )
+            .await;
+        let Some(record) = record else {
+            return;
+        };
+        let received_by = record.received_by.clone().unwrap_or_default();
+
+        if let Some(revoked) = revoke_event(&repo, &rules, record, deleted_at).await {
+            info!("[Deleted][{}] User {} lost {} experience", collection, user_did, revoked);
+        }
+        drop(permit); // Release the semaphore permit
+        drop(guard);
+
+        if received_by.is_empty() {
+            return;
+        }
+
+        // the recipients are queued as one set (see UserLocks::tickets) and waited for holding
+        // neither the user nor a permit, so no other task waits for this one meanwhile
+        let recipient_tickets = locks.tickets(received_by.values().map(String::as_str));
+        let recipient_guards = acquire_all(recipient_tickets).await;
+        let permit = semaphore.acquire_owned().await.unwrap();
+
+        let record_uri = record_uri(&user_did, &collection, &payload.commit_info.rkey);
+        for (event_type, recipient_did) in received_by {
+            let received = repo
+                .event
+                .find_event_record(recipient_did.clone(), event_type.clone(), record_uri.clone())
+                .await;
+
+            if let Some(received) = received {
+                if let Some(revoked) = revoke_event(&repo, &rules, received, deleted_at).await {
+                    info!("[Deleted][{}] User {} lost {} experience", event_type, recipient_did, revoked);
+                }
+            }
+        }
+        drop(permit);
+        drop(recipient_guards);
+    })
+}
+
+/// Subtracts the experience a deleted record originally granted and writes a compensating
+/// audit row. Returns the revoked experience, if anything was revoked.
+async fn revoke_event(
+    repository: &Arc<DatabaseRepository>,
+    rules: &ExperienceRules,
+    record: EventRecord,
+    deleted_at: u64,
+) -> Option<i64> {
+    let user_did = record.user_did.clone();
+
+    let character = repository
+        .character
+        .find_by_partition_key(user_did.clone())
+        .await;
+
+    let character_experience = repository
+        .experience
+        .find_character_experience_by_partition_key(user_did.clone())
+        .await;
+
+    let (Some(mut character), Some(character_experience)) = (character, character_experience) else {
+        repository.event.delete_event_record(&record).await;
+        return None;
+    };
+
+    // never revoke more than the user currently has
+    let current_experience = character_experience.get_experience();
+    let revoked_experience = (record.experience_gained as i64).clamp(0, current_experience);
+    let new_experience = current_experience - revoked_experience;
+    let leveling_response_dto = calculate_experience(rules.curve(), current_experience, new_experience);
+
+    repository
+        .character
+        .update_character(&mut character, leveling_response_dto.clone())
+        .await;
+
+    let audit = NewEventDTO {
+        user_did,
+        event_id: record.event_id.clone(),
+        event_type: format!("{}#delete", record.event_type),
+        posted_at: deleted_at,
+        context: EventContext::Revocation {
+            revoked_experience,
+            original_event_at: record.event_at,
+        },
+    };
+
+    repository
+        .event
+        .insert_event(&audit, leveling_response_dto)
+        .await;
+
+    repository
+        .experience
+        .decrement_character_experience(character_experience, revoked_experience)
+        .await;
+
+    repository.event.delete_event_record(&record).await;
+
+    Some(revoked_experience)
+}
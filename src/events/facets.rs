@@ -0,0 +1,102 @@
+use atrium_api::app::bsky::richtext::facet::{self, MainFeaturesItem};
+use atrium_api::types::Union::Refs;
+use std::collections::HashMap;
+
+/// Hashtags, mentions and links annotated in the text of a post, each listed once in the order
+/// they first appear.
+#[derive(Debug, Clone, Default)]
+pub struct FacetsContext {
+    /// Lowercase and without the leading `#`, so `#Rust` and `rust` are the same tag.
+    pub hashtags: Vec<String>,
+    /// DIDs of the mentioned accounts.
+    pub mentions: Vec<String>,
+    pub links: Vec<String>,
+}
+
+impl FacetsContext {
+    pub fn from_facets(facets: &[facet::Main]) -> Self {
+        let mut context = FacetsContext::default();
+
+        for feature in facets.iter().flat_map(|facet| &facet.features) {
+            let (values, value) = match feature {
+                Refs(MainFeaturesItem::Tag(tag)) => (&mut context.hashtags, normalize_hashtag(&tag.tag)),
+                Refs(MainFeaturesItem::Mention(mention)) => {
+                    (&mut context.mentions, mention.did.to_string())
+                }
+                Refs(MainFeaturesItem::Link(link)) => (&mut context.links, link.uri.clone()),
+                _ => continue,
+            };
+
+            if !value.is_empty() && !values.contains(&value) {
+                values.push(value);
+            }
+        }
+
+        context
+    }
+
+    pub fn has_hashtag(&self, tag: &str) -> bool {
+        let tag = normalize_hashtag(tag);
+
+        self.hashtags.contains(&tag)
+    }
+
+    /// The facets part of the CQL `event_data` map. Links are separated by spaces, which URIs
+    /// can't contain.
+    pub fn to_event_data(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("hashtags".to_string(), self.hashtags.join(",")),
+            ("hashtag_count".to_string(), self.hashtags.len().to_string()),
+            ("mentions".to_string(), self.mentions.join(",")),
+            ("mention_count".to_string(), self.mentions.len().to_string()),
+            ("links".to_string(), self.links.join(" ")),
+            ("link_count".to_string(), self.links.len().to_string()),
+        ])
+    }
+}
+
+pub fn normalize_hashtag(tag: &str) -> String {
+    tag.trim().trim_start_matches(['#', '＃']).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn facet(features: serde_json::Value) -> facet::Main {
+        serde_json::from_value(json!({
+            "index": { "byteStart": 0, "byteEnd": 1 },
+            "features": features,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn facets_are_collected_once_each() {
+        let facets = [
+            facet(json!([{ "$type": "app.bsky.richtext.facet#tag", "tag": "Rust" }])),
+            facet(json!([{ "$type": "app.bsky.richtext.facet#tag", "tag": "rust" }])),
+            facet(json!([{ "$type": "app.bsky.richtext.facet#mention", "did": "did:plc:friend" }])),
+            facet(json!([{ "$type": "app.bsky.richtext.facet#mention", "did": "did:plc:friend" }])),
+            facet(json!([{ "$type": "app.bsky.richtext.facet#link", "uri": "https://example.com/a,b" }])),
+        ];
+
+        let context = FacetsContext::from_facets(&facets);
+
+        assert_eq!(context.hashtags, ["rust"]);
+        assert_eq!(context.mentions, ["did:plc:friend"]);
+        assert_eq!(context.to_event_data()["links"], "https://example.com/a,b");
+        assert_eq!(context.to_event_data()["mention_count"], "1");
+    }
+
+    #[test]
+    fn hashtags_match_regardless_of_case_and_hash_sign() {
+        let facets = [facet(json!([{ "$type": "app.bsky.richtext.facet#tag", "tag": "BlueskyGame" }]))];
+
+        let context = FacetsContext::from_facets(&facets);
+
+        assert!(context.has_hashtag("#blueskygame"));
+        assert!(!context.has_hashtag("bluesky"));
+    }
+}
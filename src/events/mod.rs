@@ -1,19 +1,70 @@
+pub mod accessibility;
+pub mod context;
 pub mod create;
 mod delete;
 pub mod dto;
+pub mod facets;
+pub(crate) mod received;
+mod update;
+pub mod user_locks;
 
 use crate::events::create::create_event_handler;
+use crate::events::delete::delete_event_handler;
+use crate::events::update::update_event_handler;
+use crate::events::user_locks::UserLocks;
 use crate::repositories::DatabaseRepository;
-use jetstream_oxide::events::commit::{CommitData, CommitEvent};
+use crate::rules::RulesStore;
+use atrium_api::record::KnownRecord;
+use jetstream_oxide::events::commit::{CommitData, CommitEvent, CommitInfo};
 use jetstream_oxide::events::EventInfo;
 use std::fmt::Display;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
 
-enum AppBskyEventRecord {
+#[derive(Clone, Copy)]
+pub(crate) enum AppBskyEventRecord {
     Post,
     Like,
     Repost,
+    Follow,
+    Block,
+    ListItem,
+    StarterPack,
+}
+
+impl AppBskyEventRecord {
+    /// Every scored collection.
+    pub(crate) const ALL: [AppBskyEventRecord; 7] = [
+        AppBskyEventRecord::Post,
+        AppBskyEventRecord::Like,
+        AppBskyEventRecord::Repost,
+        AppBskyEventRecord::Follow,
+        AppBskyEventRecord::Block,
+        AppBskyEventRecord::ListItem,
+        AppBskyEventRecord::StarterPack,
+    ];
+
+    /// The scored event type of a record, if it belongs to a supported collection.
+    pub(crate) fn from_record(record: &KnownRecord) -> Option<Self> {
+        match record {
+            KnownRecord::AppBskyFeedPost(_) => Some(AppBskyEventRecord::Post),
+            KnownRecord::AppBskyFeedLike(_) => Some(AppBskyEventRecord::Like),
+            KnownRecord::AppBskyFeedRepost(_) => Some(AppBskyEventRecord::Repost),
+            KnownRecord::AppBskyGraphFollow(_) => Some(AppBskyEventRecord::Follow),
+            KnownRecord::AppBskyGraphBlock(_) => Some(AppBskyEventRecord::Block),
+            KnownRecord::AppBskyGraphListitem(_) => Some(AppBskyEventRecord::ListItem),
+            KnownRecord::AppBskyGraphStarterpack(_) => Some(AppBskyEventRecord::StarterPack),
+            _ => None,
+        }
+    }
+
+    /// The scored event type of a collection NSID, if it is supported.
+    pub(crate) fn from_collection(collection: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event_record| event_record.to_string() == collection)
+    }
 }
 
 impl Display for AppBskyEventRecord {
@@ -22,6 +73,10 @@ impl Display for AppBskyEventRecord {
             AppBskyEventRecord::Post => write!(f, "app.bsky.feed.post"),
             AppBskyEventRecord::Like => write!(f, "app.bsky.feed.like"),
             AppBskyEventRecord::Repost => write!(f, "app.bsky.feed.repost"),
+            AppBskyEventRecord::Follow => write!(f, "app.bsky.graph.follow"),
+            AppBskyEventRecord::Block => write!(f, "app.bsky.graph.block"),
+            AppBskyEventRecord::ListItem => write!(f, "app.bsky.graph.listitem"),
+            AppBskyEventRecord::StarterPack => write!(f, "app.bsky.graph.starterpack"),
         }
     }
 }
@@ -40,11 +95,32 @@ impl CreateEventPayload {
     }
 }
 
-pub async fn events_handler(
+/// Updates carry the same data as creates: the new version of the record.
+pub type UpdateEventPayload = CreateEventPayload;
+
+pub struct DeleteEventPayload {
+    event_info: EventInfo,
+    commit_info: CommitInfo,
+}
+
+impl DeleteEventPayload {
+    fn new(event_info: EventInfo, commit_info: CommitInfo) -> Self {
+        DeleteEventPayload {
+            event_info,
+            commit_info,
+        }
+    }
+}
+
+/// Dispatches `commit` to its handler without waiting. Returns the task applying it, if it
+/// wasn't ignored.
+pub fn events_handler(
     repository: &Arc<DatabaseRepository>,
     commit: CommitEvent,
+    rules: &RulesStore,
+    locks: &Arc<UserLocks>,
     semaphore: Arc<Semaphore>,
-) {
+) -> Option<JoinHandle<()>> {
     match commit {
         CommitEvent::Create {
             info: user_info,
@@ -52,13 +128,806 @@ pub async fn events_handler(
         } => {
             let payload = CreateEventPayload::new(user_info, commit);
 
-            create_event_handler(repository, payload, semaphore).await;
+            create_event_handler(repository, payload, rules, locks, semaphore)
+        }
+        CommitEvent::Delete {
+            info: user_info,
+            commit,
+        } => {
+            let payload = DeleteEventPayload::new(user_info, commit);
+
+            Some(delete_event_handler(repository, payload, rules, locks, semaphore))
+        }
+        CommitEvent::Update {
+            info: user_info,
+            commit,
+        } => {
+            let payload = UpdateEventPayload::new(user_info, commit);
+
+            Some(update_event_handler(repository, payload, rules, locks, semaphore))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leveling::calculate_experience;
+    use crate::models::character::Character;
+    use crate::models::character_experience::CharacterExperience;
+    use crate::repositories::profile_repository::{
+        FixtureProfileRepository, StalledProfileRepository, UnavailableProfileRepository,
+    };
+    use charybdis::types::Counter;
+    use futures::StreamExt;
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    const USER_DID: &str = "did:plc:testuser";
+    const MAX_WORKERS: u32 = 5;
+    const STARTING_EXPERIENCE: i64 = 1_000;
+
+    struct Harness {
+        repository: Arc<DatabaseRepository>,
+        rules: RulesStore,
+        locks: Arc<UserLocks>,
+        semaphore: Arc<Semaphore>,
+    }
+
+    impl Harness {
+        /// An in-memory database with an existing character, so no profile is fetched.
+        async fn new() -> Self {
+            Self::with_repository(DatabaseRepository::in_memory()).await
+        }
+
+        /// `repository` with the existing character.
+        async fn with_repository(repository: DatabaseRepository) -> Self {
+            let harness = Self {
+                repository: Arc::new(repository),
+                rules: RulesStore::load(None).unwrap(),
+                locks: Arc::new(UserLocks::new()),
+                semaphore: Arc::new(Semaphore::new(MAX_WORKERS as usize)),
+            };
+
+            let rules = harness.rules.current();
+            let mut character = Character {
+                user_did: USER_DID.to_string(),
+                name: "test.bsky.social".to_string(),
+                ..Default::default()
+            };
+            harness
+                .repository
+                .character
+                .update_character(
+                    &mut character,
+                    calculate_experience(rules.curve(), 0, STARTING_EXPERIENCE),
+                )
+                .await;
+            harness
+                .repository
+                .experience
+                .increment_character_experience(
+                    CharacterExperience {
+                        user_did: USER_DID.to_string(),
+                        current_experience: Counter(0),
+                    },
+                    STARTING_EXPERIENCE,
+                )
+                .await;
+
+            harness
+        }
+
+        /// Handles `commit` and waits until its spawned task finished.
+        async fn handle(&self, commit: CommitEvent) {
+            if let Some(task) = self.dispatch(commit) {
+                task.await.unwrap();
+            }
+        }
+
+        /// Dispatches `commit` without waiting, like the jetstream consumer does.
+        fn dispatch(&self, commit: CommitEvent) -> Option<JoinHandle<()>> {
+            events_handler(
+                &self.repository,
+                commit,
+                &self.rules,
+                &self.locks,
+                Arc::clone(&self.semaphore),
+            )
+        }
+
+        async fn experience(&self) -> i64 {
+            self.repository
+                .experience
+                .find_character_experience_by_partition_key(USER_DID.to_string())
+                .await
+                .unwrap()
+                .get_experience()
+        }
+
+        async fn character(&self) -> Character {
+            self.repository
+                .character
+                .find_by_partition_key(USER_DID.to_string())
+                .await
+                .unwrap()
+        }
+
+        async fn experience_gained(&self, collection: &str, rkey: &str) -> Option<i32> {
+            self.repository
+                .event
+                .find_event_record(USER_DID.to_string(), collection.to_string(), rkey.to_string())
+                .await
+                .map(|record| record.experience_gained)
+        }
+
+        async fn event_types(&self) -> Vec<String> {
+            self.repository
+                .event
+                .find_all_events()
+                .await
+                .map(|event| event.unwrap().event_type)
+                .collect()
+                .await
+        }
+    }
+
+    fn event_info(time_us: u64) -> EventInfo {
+        event_info_of(USER_DID, time_us)
+    }
+
+    fn event_info_of(did: &str, time_us: u64) -> EventInfo {
+        serde_json::from_value(json!({ "did": did, "time_us": time_us, "kind": "commit" })).unwrap()
+    }
+
+    fn commit_info(operation: &str, collection: &str, rkey: &str) -> CommitInfo {
+        serde_json::from_value(json!({
+            "operation": operation,
+            "rev": "3lfa4ptq3lk2c",
+            "rkey": rkey,
+            "collection": collection,
+        }))
+        .unwrap()
+    }
+
+    fn commit_data(operation: &str, collection: &str, rkey: &str, record: Value) -> CommitData {
+        let mut record = record;
+        record["$type"] = json!(collection);
+
+        serde_json::from_value(json!({
+            "operation": operation,
+            "rev": "3lfa4ptq3lk2c",
+            "rkey": rkey,
+            "collection": collection,
+            "cid": "bafyreig2fjxi3rptqdgylg7e5hmjl6mcke7rn2b6cugzlqq3i4zu6rq52q",
+            "record": record,
+        }))
+        .unwrap()
+    }
+
+    fn create(time_us: u64, collection: &str, rkey: &str, record: Value) -> CommitEvent {
+        CommitEvent::Create {
+            info: event_info(time_us),
+            commit: commit_data("create", collection, rkey, record),
         }
-        CommitEvent::Delete { .. } => {
-            // delete_event_handler(repository, info, commit).await;
+    }
+
+    fn update(time_us: u64, collection: &str, rkey: &str, record: Value) -> CommitEvent {
+        CommitEvent::Update {
+            info: event_info(time_us),
+            commit: commit_data("update", collection, rkey, record),
         }
-        CommitEvent::Update { .. } => {
-            // update_event_handler(repository, info, commit).await;
+    }
+
+    fn delete(time_us: u64, collection: &str, rkey: &str) -> CommitEvent {
+        CommitEvent::Delete {
+            info: event_info(time_us),
+            commit: commit_info("delete", collection, rkey),
         }
     }
+
+    fn text_post() -> Value {
+        json!({ "text": "hello world", "createdAt": "2025-01-01T00:00:00.000Z" })
+    }
+
+    fn image_post(alt: &str) -> Value {
+        images_post(&[alt])
+    }
+
+    fn images_post(alts: &[&str]) -> Value {
+        let images: Vec<_> = alts
+            .iter()
+            .map(|alt| {
+                json!({
+                    "alt": alt,
+                    "image": {
+                        "$type": "blob",
+                        "ref": { "$link": "bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy" },
+                        "mimeType": "image/jpeg",
+                        "size": 1234,
+                    },
+                })
+            })
+            .collect();
+
+        json!({
+            "text": "hello world",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "embed": { "$type": "app.bsky.embed.images", "images": images },
+        })
+    }
+
+    fn like() -> Value {
+        like_of("did:plc:author")
+    }
+
+    fn like_of(author: &str) -> Value {
+        json!({
+            "subject": subject_of(author),
+            "createdAt": "2025-01-01T00:00:00.000Z",
+        })
+    }
+
+    fn subject_of(author: &str) -> Value {
+        json!({
+            "uri": format!("at://{}/app.bsky.feed.post/3lfa4ptq3lk2c", author),
+            "cid": "bafyreig2fjxi3rptqdgylg7e5hmjl6mcke7rn2b6cugzlqq3i4zu6rq52q",
+        })
+    }
+
+    /// The harness, with the profiles of `fixtures/profiles` available for bootstrapping.
+    async fn harness_with_fixtures() -> Harness {
+        let mut repository = DatabaseRepository::in_memory();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles");
+        repository.profiles = Arc::new(FixtureProfileRepository::load(&fixtures).unwrap());
+
+        Harness::with_repository(repository).await
+    }
+
+    async fn experience_of(harness: &Harness, did: &str) -> Option<i64> {
+        harness
+            .repository
+            .experience
+            .find_character_experience_by_partition_key(did.to_string())
+            .await
+            .map(|experience| experience.get_experience())
+    }
+
+    #[tokio::test]
+    async fn created_post_is_scored_and_persisted() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        harness.handle(create(1, &post, "post1", text_post())).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 30);
+        assert_eq!(
+            harness.character().await.leveling_state.experience,
+            STARTING_EXPERIENCE + 30
+        );
+        assert_eq!(harness.experience_gained(&post, "post1").await, Some(30));
+        assert_eq!(harness.event_types().await, vec![post]);
+    }
+
+    #[tokio::test]
+    async fn first_event_bootstraps_the_character_from_its_profile() {
+        let mut repository = DatabaseRepository::in_memory();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles");
+        repository.profiles = Arc::new(FixtureProfileRepository::load(&fixtures).unwrap());
+        let harness = Harness {
+            repository: Arc::new(repository),
+            ..Harness::new().await
+        };
+        let alice = "did:plc:alicefixture00000000000";
+        let post = AppBskyEventRecord::Post.to_string();
+
+        harness
+            .handle(CommitEvent::Create {
+                info: event_info_of(alice, 1),
+                commit: commit_data("create", &post, "post1", text_post()),
+            })
+            .await;
+
+        // 42 past posts at their base experience, plus the new one
+        let character = harness
+            .repository
+            .character
+            .find_by_partition_key(alice.to_string())
+            .await
+            .unwrap();
+        assert_eq!(character.name, "alice.test");
+        assert_eq!(character.display_name, "Alice");
+        assert_eq!(character.leveling_state.experience, 42 * 30 + 30);
+
+        let experience = harness
+            .repository
+            .experience
+            .find_character_experience_by_partition_key(alice.to_string())
+            .await
+            .unwrap();
+        assert_eq!(experience.get_experience(), 42 * 30 + 30);
+    }
+
+    #[tokio::test]
+    async fn unavailable_profile_leaves_the_bootstrap_pending() {
+        let mut repository = DatabaseRepository::in_memory();
+        repository.profiles = Arc::new(UnavailableProfileRepository);
+        let harness = Harness {
+            repository: Arc::new(repository),
+            ..Harness::new().await
+        };
+        let newcomer = "did:plc:newcomer";
+        let post = AppBskyEventRecord::Post.to_string();
+
+        harness
+            .handle(CommitEvent::Create {
+                info: event_info_of(newcomer, 1),
+                commit: commit_data("create", &post, "post1", text_post()),
+            })
+            .await;
+
+        // the event still counts, the past posts are credited once the profile is available
+        let character = harness
+            .repository
+            .character
+            .find_by_partition_key(newcomer.to_string())
+            .await
+            .unwrap();
+        assert!(character.is_bootstrap_pending());
+        assert_eq!(character.leveling_state.experience, 30);
+
+        let pending: Vec<_> = harness
+            .repository
+            .character
+            .find_pending_bootstraps()
+            .await
+            .map(|pending| pending.unwrap().user_did)
+            .collect()
+            .await;
+        assert_eq!(pending, vec![newcomer.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn events_of_accounts_without_profile_are_skipped() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        // the in-memory repository knows no profiles
+        harness
+            .handle(CommitEvent::Create {
+                info: event_info_of("did:plc:suspended", 1),
+                commit: commit_data("create", &post, "post1", text_post()),
+            })
+            .await;
+
+        let character = harness
+            .repository
+            .character
+            .find_by_partition_key("did:plc:suspended".to_string())
+            .await;
+        assert!(character.is_none());
+        assert!(harness.event_types().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn image_bonuses_are_applied() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        harness.handle(create(1, &post, "post1", image_post("a cat"))).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 180);
+        assert_eq!(harness.experience_gained(&post, "post1").await, Some(180));
+    }
+
+    #[tokio::test]
+    async fn alt_text_bonus_needs_every_image_described() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        let record = images_post(&["A cat asleep on a keyboard", "img", "", "IMG_0042.jpg"]);
+        harness.handle(create(1, &post, "post1", record)).await;
+
+        assert_eq!(harness.experience_gained(&post, "post1").await, Some(130));
+        let events: Vec<_> = harness
+            .repository
+            .event
+            .find_all_events()
+            .await
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        let event_data = &events[0].event_data;
+        assert_eq!(event_data["media_count"], "4");
+        assert_eq!(event_data["described_media_count"], "1");
+        assert_eq!(event_data["alt_text_coverage"], "0.25");
+        assert_eq!(event_data["alt_texts"], "described,placeholder,missing,file_name");
+    }
+
+    #[tokio::test]
+    async fn video_alt_text_counts_as_described_media() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        let record = json!({
+            "text": "hello world",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "embed": {
+                "$type": "app.bsky.embed.video",
+                "alt": "A dog catching a frisbee",
+                "video": {
+                    "$type": "blob",
+                    "ref": { "$link": "bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy" },
+                    "mimeType": "video/mp4",
+                    "size": 1234,
+                },
+            },
+        });
+        harness.handle(create(1, &post, "post1", record)).await;
+
+        assert_eq!(harness.experience_gained(&post, "post1").await, Some(30 + 50));
+    }
+
+    #[tokio::test]
+    async fn deleted_record_revokes_its_experience() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        harness.handle(create(1, &post, "post1", text_post())).await;
+        harness.handle(delete(2, &post, "post1")).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE);
+        assert_eq!(
+            harness.character().await.leveling_state.experience,
+            STARTING_EXPERIENCE
+        );
+        assert_eq!(harness.experience_gained(&post, "post1").await, None);
+        assert_eq!(
+            harness.event_types().await,
+            vec![post.clone(), format!("{}#delete", post)]
+        );
+
+        // deleting it again revokes nothing
+        harness.handle(delete(3, &post, "post1")).await;
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE);
+    }
+
+    #[tokio::test]
+    async fn replayed_creates_are_granted_once() {
+        let harness = harness_with_fixtures().await;
+        let alice = "did:plc:alicefixture00000000000";
+        let post = AppBskyEventRecord::Post.to_string();
+        let like_type = AppBskyEventRecord::Like.to_string();
+
+        // a reconnect rewinds the cursor and delivers the same events again
+        for _ in 0..2 {
+            harness.handle(create(1, &post, "post1", text_post())).await;
+            harness.handle(create(2, &like_type, "like1", like_of(alice))).await;
+        }
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 30 + 10);
+        assert_eq!(
+            harness.character().await.leveling_state.experience,
+            STARTING_EXPERIENCE + 30 + 10
+        );
+        assert_eq!(
+            harness.event_types().await,
+            vec![
+                format!("{}#received", like_type),
+                post.clone(),
+                like_type.clone()
+            ]
+        );
+        assert_eq!(experience_of(&harness, alice).await, Some(42 * 30 + 5));
+    }
+
+    #[tokio::test]
+    async fn events_are_dated_from_their_time_us() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+        // 2025-01-01T00:00:00Z and one hour later
+        let created_us = 1_735_689_600_000_000;
+        let deleted_us = created_us + 3_600_000_000;
+
+        harness.handle(create(created_us, &post, "post1", text_post())).await;
+        harness.handle(delete(deleted_us, &post, "post1")).await;
+
+        let events: Vec<_> = harness
+            .repository
+            .event
+            .find_all_events()
+            .await
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        assert_eq!(events[0].event_at.to_rfc3339(), "2025-01-01T00:00:00+00:00");
+        assert_eq!(events[1].event_at.to_rfc3339(), "2025-01-01T01:00:00+00:00");
+        assert_eq!(events[1].event_data["original_event_at"], "2025-01-01T00:00:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn updated_record_applies_only_the_difference() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        harness.handle(create(1, &post, "post1", text_post())).await;
+        harness.handle(update(2, &post, "post1", image_post("a cat"))).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 180);
+        assert_eq!(harness.experience_gained(&post, "post1").await, Some(180));
+
+        // the same edit again is not granted twice
+        harness.handle(update(3, &post, "post1", image_post("a cat"))).await;
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 180);
+
+        harness.handle(update(4, &post, "post1", image_post(""))).await;
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 130);
+        assert_eq!(harness.experience_gained(&post, "post1").await, Some(130));
+    }
+
+    #[tokio::test]
+    async fn unscored_collections_are_ignored() {
+        let harness = Harness::new().await;
+        let list_block = "app.bsky.graph.listblock";
+
+        let record = json!({
+            "subject": "at://did:plc:author/app.bsky.graph.list/3lfa4ptq3lk2c",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+        });
+        harness.handle(create(1, list_block, "listblock1", record)).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE);
+        assert!(harness.event_types().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn follows_are_scored_and_store_their_subject() {
+        let harness = Harness::new().await;
+        let follow = AppBskyEventRecord::Follow.to_string();
+
+        let record = json!({ "subject": "did:plc:author", "createdAt": "2025-01-01T00:00:00.000Z" });
+        harness.handle(create(1, &follow, "follow1", record)).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 5);
+        let events: Vec<_> = harness
+            .repository
+            .event
+            .find_all_events()
+            .await
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        assert_eq!(events[0].event_type, follow);
+        assert_eq!(events[0].event_data["subject_did"], "did:plc:author");
+    }
+
+    #[tokio::test]
+    async fn following_yourself_grants_nothing() {
+        let harness = Harness::new().await;
+        let follow = AppBskyEventRecord::Follow.to_string();
+
+        let record = json!({ "subject": USER_DID, "createdAt": "2025-01-01T00:00:00.000Z" });
+        harness.handle(create(1, &follow, "follow1", record)).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE);
+        assert_eq!(harness.experience_gained(&follow, "follow1").await, Some(0));
+    }
+
+    #[tokio::test]
+    async fn graph_events_have_their_own_scoring() {
+        let harness = Harness::new().await;
+        let block = AppBskyEventRecord::Block.to_string();
+        let list_item = AppBskyEventRecord::ListItem.to_string();
+        let starter_pack = AppBskyEventRecord::StarterPack.to_string();
+
+        let record = json!({ "subject": "did:plc:troll", "createdAt": "2025-01-01T00:00:00.000Z" });
+        harness.handle(create(1, &block, "block1", record)).await;
+        let record = json!({
+            "subject": "did:plc:author",
+            "list": "at://did:plc:testuser/app.bsky.graph.list/3lfa4ptq3lk2c",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+        });
+        harness.handle(create(2, &list_item, "item1", record)).await;
+        let record = json!({
+            "name": "Rustaceans",
+            "list": "at://did:plc:testuser/app.bsky.graph.list/3lfa4ptq3lk2c",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+        });
+        harness.handle(create(3, &starter_pack, "pack1", record)).await;
+
+        assert_eq!(harness.experience_gained(&block, "block1").await, Some(0));
+        assert_eq!(harness.experience_gained(&list_item, "item1").await, Some(5));
+        assert_eq!(harness.experience_gained(&starter_pack, "pack1").await, Some(50));
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 55);
+        assert_eq!(
+            harness.event_types().await,
+            vec![block.clone(), list_item.clone(), starter_pack.clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn likes_credit_the_author_of_the_liked_post() {
+        let harness = harness_with_fixtures().await;
+        let alice = "did:plc:alicefixture00000000000";
+        let like_type = AppBskyEventRecord::Like.to_string();
+
+        harness.handle(create(1, &like_type, "like1", like_of(alice))).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 10);
+        // bootstrapped from the profile (42 past posts), plus the received like
+        assert_eq!(experience_of(&harness, alice).await, Some(42 * 30 + 5));
+        let received = harness
+            .repository
+            .event
+            .find_event_record(
+                alice.to_string(),
+                format!("{}#received", like_type),
+                format!("at://{}/{}/like1", USER_DID, like_type),
+            )
+            .await
+            .unwrap();
+        assert_eq!(received.experience_gained, 5);
+
+        // a replayed like isn't credited twice
+        harness.handle(create(1, &like_type, "like1", like_of(alice))).await;
+        assert_eq!(experience_of(&harness, alice).await, Some(42 * 30 + 5));
+    }
+
+    #[tokio::test]
+    async fn self_likes_credit_nobody() {
+        let harness = Harness::new().await;
+        let like_type = AppBskyEventRecord::Like.to_string();
+        let repost_type = AppBskyEventRecord::Repost.to_string();
+
+        harness.handle(create(1, &like_type, "like1", like_of(USER_DID))).await;
+        harness.handle(create(2, &repost_type, "repost1", like_of(USER_DID))).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 20);
+        assert_eq!(harness.event_types().await, vec![like_type, repost_type]);
+    }
+
+    #[tokio::test]
+    async fn replies_and_quotes_credit_their_authors() {
+        let harness = harness_with_fixtures().await;
+        let alice = "did:plc:alicefixture00000000000";
+        let post = AppBskyEventRecord::Post.to_string();
+
+        let record = json!({
+            "text": "quoting you in your own thread",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "reply": { "root": subject_of(alice), "parent": subject_of(alice) },
+            "embed": { "$type": "app.bsky.embed.record", "record": subject_of(alice) },
+        });
+        harness.handle(create(1, &post, "post1", record)).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 30);
+        assert_eq!(experience_of(&harness, alice).await, Some(42 * 30 + 10 + 10));
+    }
+
+    #[tokio::test]
+    async fn credits_sharing_a_timestamp_are_all_kept() {
+        let harness = harness_with_fixtures().await;
+        let alice = "did:plc:alicefixture00000000000";
+        let post = AppBskyEventRecord::Post.to_string();
+
+        let record = json!({
+            "text": "quoting you in your own thread",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "reply": { "root": subject_of(alice), "parent": subject_of(alice) },
+            "embed": { "$type": "app.bsky.embed.record", "record": subject_of(alice) },
+        });
+        harness.handle(create(1, &post, "post1", record)).await;
+
+        let mut history: Vec<String> = harness
+            .repository
+            .event
+            .find_all_events()
+            .await
+            .map(|event| event.unwrap())
+            .filter(|event| futures::future::ready(event.user_did == alice))
+            .map(|event| event.event_type)
+            .collect()
+            .await;
+        history.sort();
+        assert_eq!(
+            history,
+            vec![format!("{post}#quote_received"), format!("{post}#reply_received")]
+        );
+    }
+
+    #[tokio::test]
+    async fn deleting_a_like_revokes_the_received_experience() {
+        let harness = harness_with_fixtures().await;
+        let alice = "did:plc:alicefixture00000000000";
+        let like_type = AppBskyEventRecord::Like.to_string();
+
+        harness.handle(create(1, &like_type, "like1", like_of(alice))).await;
+        harness.handle(delete(2, &like_type, "like1")).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE);
+        assert_eq!(experience_of(&harness, alice).await, Some(42 * 30));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn interleaved_creates_and_deletes_of_one_user_are_applied_exactly() {
+        let harness = Harness::new().await;
+        let post = AppBskyEventRecord::Post.to_string();
+
+        // every third post is deleted right after the next one was created
+        let mut tasks = Vec::new();
+        let mut time_us = 0;
+        for event in 0..60 {
+            time_us += 1;
+            tasks.extend(harness.dispatch(create(time_us, &post, &format!("post{}", event), text_post())));
+            if event % 3 == 1 {
+                time_us += 1;
+                tasks.extend(harness.dispatch(delete(time_us, &post, &format!("post{}", event - 1))));
+            }
+        }
+        futures::future::join_all(tasks).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 40 * 30);
+        assert_eq!(
+            harness.character().await.leveling_state.experience,
+            STARTING_EXPERIENCE + 40 * 30
+        );
+        for event in 0..60 {
+            let expected = (event % 3 != 0).then_some(30);
+            assert_eq!(
+                harness.experience_gained(&post, &format!("post{}", event)).await,
+                expected
+            );
+        }
+        assert_eq!(harness.event_types().await.len(), 60 + 20);
+    }
+
+    #[tokio::test]
+    async fn a_stalled_user_does_not_hold_up_the_others() {
+        let profiles = Arc::new(StalledProfileRepository::new());
+        let mut repository = DatabaseRepository::in_memory();
+        repository.profiles = Arc::clone(&profiles) as _;
+        let harness = Harness::with_repository(repository).await;
+        let post = AppBskyEventRecord::Post.to_string();
+        let stalled = "did:plc:stalled";
+
+        // the newcomer's first event waits for their profile, the others for the first one;
+        // there are more of them than workers
+        let events = MAX_WORKERS as u64 + 1;
+        let stalled_tasks: Vec<_> = (1..=events)
+            .filter_map(|event| {
+                harness.dispatch(CommitEvent::Create {
+                    info: event_info_of(stalled, event),
+                    commit: commit_data("create", &post, &format!("post{}", event), text_post()),
+                })
+            })
+            .collect();
+
+        harness.handle(create(100, &post, "post1", text_post())).await;
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 30);
+        assert_eq!(experience_of(&harness, stalled).await, None);
+
+        profiles.release();
+        futures::future::join_all(stalled_tasks).await;
+        assert_eq!(experience_of(&harness, stalled).await, Some(events as i64 * 30));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn burst_of_events_is_applied_exactly() {
+        let harness = Harness::new().await;
+        let like_type = AppBskyEventRecord::Like.to_string();
+
+        let tasks: Vec<_> = (0..50)
+            .filter_map(|event| {
+                harness.dispatch(create(event + 1, &like_type, &format!("like{}", event), like()))
+            })
+            .collect();
+        futures::future::join_all(tasks).await;
+
+        assert_eq!(harness.experience().await, STARTING_EXPERIENCE + 50 * 10);
+        assert_eq!(
+            harness.character().await.leveling_state.experience,
+            STARTING_EXPERIENCE + 50 * 10
+        );
+        assert_eq!(harness.event_types().await.len(), 50);
+    }
 }
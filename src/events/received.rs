@@ -0,0 +1,91 @@
+use crate::events::context::{repository_did, EventContext, ReceivedContext};
+use crate::events::create::grant_experience;
+use crate::events::dto::NewEventDTO;
+use crate::leveling::LevelResponse;
+use crate::repositories::DatabaseRepository;
+use crate::rules::ExperienceRules;
+use std::sync::Arc;
+
+/// Engagement of an event with a record of another account, credited to that account.
+pub(crate) struct ReceivedEngagement {
+    pub recipient_did: String,
+    /// Event type of the credit, e.g. `app.bsky.feed.like#received`.
+    pub event_type: String,
+    /// The recipient's record that was engaged with.
+    pub subject_uri: String,
+}
+
+impl ReceivedEngagement {
+    /// The recipient's event. It is keyed by the AT-URI of the engaging record, so deleting that
+    /// record finds it again.
+    pub(crate) fn to_payload(&self, payload: &NewEventDTO) -> NewEventDTO {
+        NewEventDTO {
+            user_did: self.recipient_did.clone(),
+            event_id: record_uri(&payload.user_did, &payload.event_type, &payload.event_id),
+            event_type: self.event_type.clone(),
+            posted_at: payload.posted_at,
+            context: EventContext::Received(ReceivedContext {
+                actor_did: payload.user_did.clone(),
+                subject_uri: self.subject_uri.clone(),
+            }),
+        }
+    }
+}
+
+/// Records of other accounts that `payload` engages with: the liked or reposted record, the
+/// parent of a reply and the quoted post. Engaging with your own records credits nobody.
+pub(crate) fn received_engagements(payload: &NewEventDTO) -> Vec<ReceivedEngagement> {
+    let subjects = match &payload.context {
+        EventContext::Like(subject) | EventContext::Repost(subject) => {
+            vec![("received", &subject.uri)]
+        }
+        EventContext::Post(post) => post
+            .reply
+            .iter()
+            .map(|reply| ("reply_received", &reply.parent_uri))
+            .chain(post.quote_uri.iter().map(|uri| ("quote_received", uri)))
+            .collect(),
+        _ => vec![],
+    };
+
+    subjects
+        .into_iter()
+        .filter_map(|(kind, uri)| {
+            let recipient_did = repository_did(uri)?;
+
+            (recipient_did != payload.user_did).then(|| ReceivedEngagement {
+                recipient_did,
+                event_type: format!("{}#{}", payload.event_type, kind),
+                subject_uri: uri.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Credits `engagement` to its recipient, at most once per engaging record (see
+/// [`grant_experience`]). Returns `None` if nothing was credited.
+pub(crate) async fn credit_received(
+    repository: &Arc<DatabaseRepository>,
+    rules: &ExperienceRules,
+    payload: &NewEventDTO,
+    engagement: &ReceivedEngagement,
+) -> Option<LevelResponse> {
+    let experience = rules.received_experience(&payload.event_type);
+    if experience == 0 {
+        return None;
+    }
+
+    grant_experience(repository, rules, &engagement.to_payload(payload), experience).await
+}
+
+/// Whether `event_type` credits engagement received from another account.
+pub(crate) fn is_received(event_type: &str) -> bool {
+    event_type
+        .rsplit_once('#')
+        .is_some_and(|(_, kind)| kind.ends_with("received"))
+}
+
+/// `at://<did>/<collection>/<rkey>`
+pub(crate) fn record_uri(user_did: &str, collection: &str, rkey: &str) -> String {
+    format!("at://{}/{}/{}", user_did, collection, rkey)
+}
@@ -0,0 +1,150 @@
+use crate::events::create::{select_event_handler, CreateEventHandler};
+use crate::events::context::EventContext;
+use crate::events::dto::NewEventDTO;
+use crate::events::user_locks::UserLocks;
+use crate::events::UpdateEventPayload;
+use crate::leveling::calculate_experience;
+use crate::models::character::CharacterProfile;
+use crate::repositories::DatabaseRepository;
+use crate::rules::RulesStore;
+use atrium_api::app::bsky::actor::profile;
+use atrium_api::record::KnownRecord;
+use paris::info;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// Dispatches an update event without waiting, like [`crate::events::create::create_event_handler`].
+pub fn update_event_handler(
+    repository: &Arc<DatabaseRepository>,
+    payload: UpdateEventPayload,
+    rules: &RulesStore,
+    locks: &UserLocks,
+    semaphore: Arc<Semaphore>,
+) -> JoinHandle<()> {
+    let handler = select_event_handler(&payload.commit_data.record, rules);
+
+    let repo = Arc::clone(repository);
+    let ticket = locks.ticket(payload.event_info.did.as_str()); // Queue behind the user's previous events
+
+    tokio::spawn(async move {
+        let guard = ticket.acquire().await;
+        let permit = semaphore.acquire_owned().await.unwrap(); // Acquire a semaphore permit
+        let user_did = payload.event_info.did.as_str().to_string();
+
+        if let KnownRecord::AppBskyActorProfile(record) = &payload.commit_data.record {
+            if update_profile(&repo, user_did.clone(), record).await {
+                info!("[Updated][app.bsky.actor.profile] User {} refreshed profile", user_did);
+            }
+        } else if let Some(handler) = handler {
+            let event_payload = NewEventDTO::from(&payload);
+
+            if let Some(delta) = rescore_event(&repo, handler.as_ref(), &event_payload).await {
+                info!(
+                    "[Updated][{}] User {} experience changed by {}",
+                    event_payload.event_type, user_did, delta
+                );
+            }
+        }
+        drop(permit); // Release the semaphore permit
+        drop(guard);
+    })
+}
+
+/// Refreshes the display data of an existing character. Profile edits never create characters.
+async fn update_profile(
+    repository: &Arc<DatabaseRepository>,
+    user_did: String,
+    record: &profile::Record,
+) -> bool {
+    if repository
+        .character
+        .find_by_partition_key(user_did.clone())
+        .await
+        .is_none()
+    {
+        return false;
+    }
+
+    let profile = CharacterProfile::from_record(user_did, record);
+    repository.character.update_character_profile(&profile).await;
+
+    true
+}
+
+/// Re-scores an edited record and applies only the difference to what it originally granted,
+/// so edits can never double-grant. Returns the applied delta, if any.
+async fn rescore_event(
+    repository: &Arc<DatabaseRepository>,
+    handler: &(dyn CreateEventHandler + Send + Sync),
+    payload: &NewEventDTO,
+) -> Option<i64> {
+    // records we never scored are not re-scored either
+    let mut record = repository
+        .event
+        .find_event_record(
+            payload.user_did.clone(),
+            payload.event_type.clone(),
+            payload.event_id.clone(),
+        )
+        .await?;
+
+    let delta = handler.calculate_exp(payload) as i64 - record.experience_gained as i64;
+    if delta == 0 {
+        return None;
+    }
+
+    let mut character = repository
+        .character
+        .find_by_partition_key(payload.user_did.clone())
+        .await?;
+
+    let character_experience = repository
+        .experience
+        .find_character_experience_by_partition_key(payload.user_did.clone())
+        .await?;
+
+    let current_experience = character_experience.get_experience();
+    let new_experience = current_experience.saturating_add(delta).max(0);
+    let applied_delta = new_experience - current_experience;
+    let leveling_response_dto = calculate_experience(handler.rules().curve(), current_experience, new_experience);
+
+    repository
+        .character
+        .update_character(&mut character, leveling_response_dto.clone())
+        .await;
+
+    let audit = NewEventDTO {
+        user_did: payload.user_did.clone(),
+        event_id: payload.event_id.clone(),
+        event_type: format!("{}#update", payload.event_type),
+        posted_at: payload.posted_at,
+        context: EventContext::Rescore {
+            context: Box::new(payload.context.clone()),
+            experience_delta: applied_delta,
+        },
+    };
+
+    repository
+        .event
+        .insert_event(&audit, leveling_response_dto)
+        .await;
+
+    if applied_delta > 0 {
+        repository
+            .experience
+            .increment_character_experience(character_experience, applied_delta)
+            .await;
+    } else {
+        repository
+            .experience
+            .decrement_character_experience(character_experience, -applied_delta)
+            .await;
+    }
+
+    // the applied delta never exceeds the re-scored delta, which fits the per-event i32
+    record.experience_gained += applied_delta as i32;
+    repository.event.update_event_record(&record).await;
+
+    Some(applied_delta)
+}
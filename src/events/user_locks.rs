@@ -0,0 +1,288 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// The last ticket handed out per user.
+type Queues = Mutex<HashMap<String, Waiting>>;
+
+/// A ticket that others may have to wait for.
+struct Waiting {
+    id: u64,
+    released: oneshot::Receiver<Released>,
+}
+
+/// Sent to the next ticket once a ticket is released: what that one still has to wait for,
+/// if it was given up before its turn.
+struct Released(Option<Waiting>);
+
+/// One queue per user DID, so each user's events are applied one at a time and in the order
+/// they were received, while events of different users still run concurrently.
+///
+/// The handlers read the character, compute and write it back; without this two events of
+/// the same user could interleave and persist a stale leveling state, or bootstrap the same
+/// character twice.
+#[derive(Default)]
+pub struct UserLocks {
+    queues: Arc<Queues>,
+    next_id: AtomicU64,
+}
+
+impl UserLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the next place in the queue of `user_did`, without waiting. The ticket is meant
+    /// to be moved into the task processing the event and acquired there.
+    ///
+    /// Take it from the dispatcher, before spawning: events are applied in the order their
+    /// tickets were taken, and one busy user never holds up the events of everybody else.
+    pub fn ticket(&self, user_did: &str) -> UserTicket {
+        let mut queues = self.queues.lock().unwrap();
+        self.enqueue(&mut queues, user_did)
+    }
+
+    /// Takes a ticket for each distinct user of `user_dids`, in DID order.
+    ///
+    /// The whole set is queued at once, so for any two sets sharing users one of them is ahead
+    /// in every shared queue and tasks holding several tickets never wait for each other in a
+    /// cycle, wherever the tickets are taken.
+    pub fn tickets<'a>(&self, user_dids: impl IntoIterator<Item = &'a str>) -> Vec<UserTicket> {
+        let user_dids: BTreeSet<_> = user_dids.into_iter().collect();
+        let mut queues = self.queues.lock().unwrap();
+
+        user_dids
+            .into_iter()
+            .map(|user_did| self.enqueue(&mut queues, user_did))
+            .collect()
+    }
+
+    fn enqueue(&self, queues: &mut HashMap<String, Waiting>, user_did: &str) -> UserTicket {
+        let (release, released) = oneshot::channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let previous = queues.insert(user_did.to_string(), Waiting { id, released });
+
+        UserTicket {
+            place: Place {
+                user_did: user_did.to_string(),
+                id,
+                queues: Arc::clone(&self.queues),
+                previous,
+                release: Some(release),
+            },
+        }
+    }
+
+    /// Waits until no other event of `user_did` is in flight, in the calling task. For work
+    /// outside the event stream, such as the bootstrap reconciliation.
+    pub async fn lock(&self, user_did: &str) -> UserGuard {
+        self.ticket(user_did).acquire().await
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.queues.lock().unwrap().len()
+    }
+}
+
+/// A place in the queue of a user, see [`UserLocks::ticket`]. Dropping it before acquiring it
+/// gives up the place without letting the next ticket overtake the previous ones.
+pub struct UserTicket {
+    place: Place,
+}
+
+impl UserTicket {
+    /// Waits until the events queued before this one are applied.
+    pub async fn acquire(mut self) -> UserGuard {
+        let mut previous = self.place.previous.take();
+        while let Some(waiting) = previous {
+            previous = waiting
+                .released
+                .await
+                .ok()
+                .and_then(|Released(handed_over)| handed_over);
+        }
+
+        UserGuard { _place: self.place }
+    }
+}
+
+/// Acquires `tickets` one after the other, see [`UserLocks::tickets`].
+pub async fn acquire_all(tickets: Vec<UserTicket>) -> Vec<UserGuard> {
+    let mut guards = Vec::with_capacity(tickets.len());
+    for ticket in tickets {
+        guards.push(ticket.acquire().await);
+    }
+
+    guards
+}
+
+/// Lets the next event of the user go once dropped.
+pub struct UserGuard {
+    _place: Place,
+}
+
+struct Place {
+    user_did: String,
+    id: u64,
+    queues: Arc<Queues>,
+    /// The ticket to wait for, until acquired.
+    previous: Option<Waiting>,
+    release: Option<oneshot::Sender<Released>>,
+}
+
+impl Drop for Place {
+    fn drop(&mut self) {
+        let mut queues = self.queues.lock().unwrap();
+        let previous = self.previous.take();
+
+        let is_last = queues
+            .get(&self.user_did)
+            .is_some_and(|last| last.id == self.id);
+        if is_last {
+            // nobody waits for us: forget the queue, or give it back to the ticket we waited for
+            match previous {
+                Some(previous) => queues.insert(self.user_did.clone(), previous),
+                None => queues.remove(&self.user_did),
+            };
+        } else if let Some(release) = self.release.take() {
+            release.send(Released(previous)).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::task::JoinSet;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn contended_locks_are_held_one_at_a_time() {
+        let locks = Arc::new(UserLocks::new());
+        let holders = Arc::new(AtomicU64::new(0));
+        let mut tasks = JoinSet::new();
+
+        for _ in 0..500 {
+            let locks = Arc::clone(&locks);
+            let holders = Arc::clone(&holders);
+
+            tasks.spawn(async move {
+                let _guard = locks.lock("did:plc:hammered").await;
+                assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                tokio::task::yield_now().await;
+                holders.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        tasks.join_all().await;
+
+        assert_eq!(locks.len(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn events_are_applied_in_ticket_order() {
+        let locks = UserLocks::new();
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = JoinSet::new();
+
+        for event in 0..100 {
+            let ticket = locks.ticket("did:plc:ordered");
+            let applied = Arc::clone(&applied);
+
+            tasks.spawn(async move {
+                let _guard = ticket.acquire().await;
+                tokio::task::yield_now().await;
+                applied.lock().unwrap().push(event);
+            });
+        }
+        tasks.join_all().await;
+
+        assert_eq!(*applied.lock().unwrap(), (0..100).collect::<Vec<_>>());
+        assert_eq!(locks.len(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn tasks_locking_the_same_users_in_any_order_do_not_deadlock() {
+        let locks = UserLocks::new();
+        let mut tasks = JoinSet::new();
+
+        for event in 0..200 {
+            // alternately the actor and the recipient of each other's events
+            let (actor, recipient) = if event % 2 == 0 {
+                ("did:plc:a", "did:plc:b")
+            } else {
+                ("did:plc:b", "did:plc:a")
+            };
+            let tickets = locks.tickets([actor, recipient, actor]);
+            assert_eq!(tickets.len(), 2);
+
+            tasks.spawn(async move {
+                let _guards = acquire_all(tickets).await;
+                tokio::task::yield_now().await;
+            });
+        }
+        tasks.join_all().await;
+
+        assert_eq!(locks.len(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn overlapping_sets_taken_concurrently_do_not_deadlock() {
+        let locks = Arc::new(UserLocks::new());
+        let sets = [
+            ["did:plc:a", "did:plc:b", "did:plc:c"],
+            ["did:plc:b", "did:plc:c", "did:plc:d"],
+            ["did:plc:c", "did:plc:d", "did:plc:a"],
+            ["did:plc:d", "did:plc:a", "did:plc:b"],
+        ];
+        let mut tasks = JoinSet::new();
+
+        // every task takes its tickets from inside the task, racing the others
+        for event in 0..2_000 {
+            let locks = Arc::clone(&locks);
+            let set = sets[event % sets.len()];
+
+            tasks.spawn(async move {
+                let _guards = acquire_all(locks.tickets(set)).await;
+                tokio::task::yield_now().await;
+            });
+        }
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(10), tasks.join_all()).await;
+
+        assert!(finished.is_ok(), "tasks waiting for each other");
+        assert_eq!(locks.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn different_users_do_not_wait_for_each_other() {
+        let locks = UserLocks::new();
+
+        let _first = locks.lock("did:plc:first").await;
+        let _second = locks.lock("did:plc:second").await;
+
+        assert_eq!(locks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn given_up_tickets_are_not_overtaken() {
+        let locks = UserLocks::new();
+
+        let first = locks.lock("did:plc:queued").await;
+        let given_up = locks.ticket("did:plc:queued");
+        let third = tokio::spawn(locks.ticket("did:plc:queued").acquire());
+        drop(given_up);
+        tokio::task::yield_now().await;
+        assert!(!third.is_finished());
+
+        drop(first);
+        drop(third.await.unwrap());
+        assert_eq!(locks.len(), 0);
+
+        // giving up the last ticket hands the queue back to the one before
+        let first = locks.lock("did:plc:queued").await;
+        drop(locks.ticket("did:plc:queued"));
+        assert_eq!(locks.len(), 1);
+        drop(first);
+        assert_eq!(locks.len(), 0);
+    }
+}
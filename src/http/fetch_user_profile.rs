@@ -1,10 +1,8 @@
 use crate::http::AppState;
 use crate::models::character::Character;
-
-use crate::models::character_experience::CharacterExperience;
 use actix_web::{get, web, HttpResponse, Responder};
-use charybdis::types::Counter;
-use paris::info;
+use crate::repositories::profile_repository::ProfileError;
+use paris::{info, warn};
 use serde_json::json;
 
 #[get("/find/{profile_did}")]
@@ -25,28 +23,73 @@ pub async fn handle(
         None => {
             let response = app
                 .repository
-                .bsky
+                .profiles
                 .get_author_profile(profile_did.clone())
                 .await;
-            info!("Creating new character for user {}", profile_did);
-            let character = Character::from(response);
-
-            let character_experience = CharacterExperience {
-                user_did: profile_did.clone(),
-                current_experience: Counter(0),
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("Failed to fetch the profile of user {}: {}", profile_did, e);
+                    return Ok(profile_error_response(&e));
+                }
             };
-
-            app.repository
-                .character
-                .increment_character_experience(
-                    character_experience,
-                    character.leveling_state.experience as i64,
-                )
-                .await;
-
-            character
+            // read-only: the character is only created by the user's first event, under their
+            // user lock, so lookups can't race the ingestion or grant the bootstrap twice
+            info!("Previewing character for unknown user {}", profile_did);
+            Character::from_profile(response, &app.rules.current())
         }
     };
 
     Ok(HttpResponse::Ok().json(json!(character)))
 }
+
+fn profile_error_response(error: &ProfileError) -> HttpResponse {
+    let mut response = match error {
+        ProfileError::NotFound { .. } => HttpResponse::NotFound(),
+        ProfileError::Invalid(_) => HttpResponse::BadRequest(),
+        ProfileError::RateLimited | ProfileError::Unavailable(_) => HttpResponse::ServiceUnavailable(),
+    };
+
+    response.json(json!({ "error": error.to_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jetstream::ConnectionState;
+    use crate::repositories::profile_repository::FixtureProfileRepository;
+    use crate::repositories::DatabaseRepository;
+    use crate::rules::RulesStore;
+    use actix_web::{test, App};
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::sync::watch;
+
+    #[actix_web::test]
+    async fn unknown_users_are_previewed_without_being_stored() {
+        let mut repository = DatabaseRepository::in_memory();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles");
+        repository.profiles = Arc::new(FixtureProfileRepository::load(&fixtures).unwrap());
+        let repository = Arc::new(repository);
+        let state = web::Data::new(AppState {
+            repository: Arc::clone(&repository),
+            jetstream_state: watch::channel(ConnectionState::Disconnected).1,
+            rules: Arc::new(RulesStore::load(None).unwrap()),
+        });
+        let app = test::init_service(App::new().app_data(state).service(handle)).await;
+        let alice = "did:plc:alicefixture00000000000";
+
+        for _ in 0..2 {
+            let request = test::TestRequest::get().uri(&format!("/find/{}", alice)).to_request();
+            let character: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+            assert_eq!(character["leveling_state"]["experience"], 42 * 30);
+        }
+
+        let experience = repository
+            .experience
+            .find_character_experience_by_partition_key(alice.to_string())
+            .await;
+        assert!(experience.is_none());
+        assert!(repository.character.find_by_partition_key(alice.to_string()).await.is_none());
+    }
+}
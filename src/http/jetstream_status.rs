@@ -0,0 +1,10 @@
+use crate::http::AppState;
+use actix_web::{get, web, HttpResponse, Responder};
+use serde_json::json;
+
+#[get("/status/jetstream")]
+pub async fn handle(app: web::Data<AppState>) -> actix_web::Result<impl Responder> {
+    let state = app.jetstream_state.borrow().clone();
+
+    Ok(HttpResponse::Ok().json(json!(state)))
+}
@@ -1,23 +1,55 @@
 mod fetch_user_profile;
+mod jetstream_status;
+mod profile_cache_status;
 
-use crate::http::fetch_user_profile::handle;
+use crate::args::HttpSettings;
+use crate::jetstream::ConnectionState;
 use crate::repositories::DatabaseRepository;
+use crate::rules::RulesStore;
 use actix_web::web::Data;
 use actix_web::{App, HttpServer};
 use std::sync::Arc;
+use tokio::sync::watch;
 
 struct AppState {
     repository: Arc<DatabaseRepository>,
+    jetstream_state: watch::Receiver<ConnectionState>,
+    rules: Arc<RulesStore>,
 }
 
-pub async fn start_http(repository: &Arc<DatabaseRepository>) -> std::io::Result<()> {
+/// Serves the API until `shutdown` fires, then stops accepting connections and lets
+/// in-flight requests finish.
+pub async fn start_http(
+    settings: &HttpSettings,
+    repository: &Arc<DatabaseRepository>,
+    jetstream_state: watch::Receiver<ConnectionState>,
+    rules: Arc<RulesStore>,
+    mut shutdown: watch::Receiver<bool>,
+) -> std::io::Result<()> {
     let repository = Arc::clone(repository);
 
-    let app_state = Data::new(AppState { repository });
-    HttpServer::new(move || App::new().app_data(app_state.clone()).service(handle))
-        .bind(("0.0.0.0", 8000))
-        .unwrap()
-        .workers(1)
-        .run()
-        .await
+    let app_state = Data::new(AppState {
+        repository,
+        jetstream_state,
+        rules,
+    });
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_state.clone())
+            .service(fetch_user_profile::handle)
+            .service(jetstream_status::handle)
+            .service(profile_cache_status::handle)
+    })
+    .bind((settings.http_host.as_str(), settings.http_port))?
+    .workers(settings.http_workers)
+    .disable_signals() // shutdown is coordinated by main
+    .run();
+
+    let handle = server.handle();
+    tokio::spawn(async move {
+        let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+        handle.stop(true).await;
+    });
+
+    server.await
 }
@@ -0,0 +1,10 @@
+use crate::http::AppState;
+use actix_web::{get, web, HttpResponse, Responder};
+use serde_json::json;
+
+#[get("/status/profiles")]
+pub async fn handle(app: web::Data<AppState>) -> actix_web::Result<impl Responder> {
+    let stats = app.repository.profiles.cache_stats();
+
+    Ok(HttpResponse::Ok().json(json!({ "cache": stats })))
+}
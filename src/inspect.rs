@@ -0,0 +1,33 @@
+use crate::repositories::DatabaseRepository;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Prints the stored character and experience counter of `user_did` as JSON.
+/// Returns `false` if the user has no character.
+pub async fn inspect_character(repository: &Arc<DatabaseRepository>, user_did: String) -> bool {
+    let Some(character) = repository
+        .character
+        .find_by_partition_key(user_did.clone())
+        .await
+    else {
+        eprintln!("No character found for user {}", user_did);
+        return false;
+    };
+
+    let current_experience = repository
+        .experience
+        .find_character_experience_by_partition_key(user_did)
+        .await
+        .map(|character_experience| character_experience.get_experience());
+
+    let output = json!({
+        "character": character,
+        "current_experience": current_experience,
+    });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&output).expect("Failed to serialize character")
+    );
+
+    true
+}
@@ -1,45 +1,658 @@
-use crate::events::events_handler;
+use crate::events::{events_handler, AppBskyEventRecord};
+use crate::events::user_locks::UserLocks;
+use crate::reconcile::run_reconciler;
 use crate::repositories::DatabaseRepository;
-use atrium_api::types::string::{Did, Nsid};
+use crate::rules::RulesStore;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use jetstream_oxide::events::commit::CommitEvent;
 use jetstream_oxide::events::JetstreamEvent::Commit;
 use jetstream_oxide::{
     DefaultJetstreamEndpoints, JetstreamCompression, JetstreamConfig, JetstreamConnector,
+    JetstreamReceiver,
 };
-use paris::info;
+use paris::{info, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::time::Duration;
+use tokio::sync::{watch, Semaphore};
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+use tokio::time::Instant;
 use crate::args::AppSettings;
 
-pub async fn start_jetstream(settings: Arc<AppSettings>, repository: &Arc<DatabaseRepository>) {
+/// Current state of the Jetstream connection, published to the rest of the app.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting { endpoint: String },
+    Connected { endpoint: String },
+    Reconnecting { attempt: u32, retry_in_ms: u64 },
+}
+
+/// What every received event is dispatched with; shared across reconnects so in-flight
+/// events keep their per-user ordering and are accounted for on shutdown.
+struct Dispatcher {
+    rules: Arc<RulesStore>,
+    locks: Arc<UserLocks>,
+    semaphore: Arc<Semaphore>,
+}
+
+/// Supervises the Jetstream listener: connects, consumes events until the stream drops,
+/// then reconnects with exponential backoff, rotating through the configured endpoints
+/// and resuming from the last processed cursor.
+///
+/// Pending character bootstraps are reconciled in the background, see [`run_reconciler`].
+///
+/// Once `shutdown` fires it stops consuming, waits for the in-flight event tasks and persists
+/// the final cursor. Returns `false` if the tasks didn't finish within the shutdown timeout.
+pub async fn start_jetstream(
+    settings: Arc<AppSettings>,
+    repository: &Arc<DatabaseRepository>,
+    rules: Arc<RulesStore>,
+    state: watch::Sender<ConnectionState>,
+    mut shutdown: watch::Receiver<bool>,
+) -> bool {
+    let endpoints = jetstream_endpoints(&settings);
+    for topic in &settings.bsky_topics {
+        // profile records only refresh existing characters, see the update handler
+        if AppBskyEventRecord::from_collection(topic).is_none() && topic.as_str() != "app.bsky.actor.profile" {
+            warn!("Events of {} are not scored and will be ignored", topic.as_str());
+        }
+    }
+
+    let dispatcher = Dispatcher {
+        rules,
+        locks: Arc::new(UserLocks::new()),
+        semaphore: Arc::new(Semaphore::new(settings.max_workers)),
+    };
+
+    let reconciler = (settings.bootstrap_reconcile_interval_seconds > 0).then(|| {
+        tokio::spawn(run_reconciler(
+            Arc::clone(repository),
+            Arc::clone(&dispatcher.rules),
+            Arc::clone(&dispatcher.locks),
+            Duration::from_secs(settings.bootstrap_reconcile_interval_seconds),
+            shutdown.clone(),
+        ))
+    });
+
+    let mut progress = Progress::resume(
+        repository
+            .cursor
+            .find_cursor(settings.instance_id.clone())
+            .await
+            .map(|cursor| cursor.time_us as u64),
+        settings.max_workers * PENDING_EVENTS_PER_WORKER,
+    );
+
+    let mut attempt: u32 = 0;
+    let mut endpoint_index = 0;
+
+    while !*shutdown.borrow() {
+        let endpoint = endpoints[endpoint_index % endpoints.len()].clone();
+        state.send_replace(ConnectionState::Connecting {
+            endpoint: endpoint.clone(),
+        });
+
+        let cursor = rewind_cursor(&settings, progress.checkpoint());
+
+        match connect(&settings, endpoint.clone(), cursor).await {
+            Ok(connection) => {
+                info!("Connected to Jetstream at {}", endpoint);
+                attempt = 0;
+                state.send_replace(ConnectionState::Connected {
+                    endpoint: endpoint.clone(),
+                });
+
+                consume(
+                    &settings,
+                    repository,
+                    connection,
+                    &dispatcher,
+                    &mut progress,
+                    &mut shutdown,
+                )
+                .await;
+
+                if *shutdown.borrow() {
+                    break;
+                }
+
+                warn!("Connection to Jetstream at {} lost", endpoint);
+                save_cursor(&settings, repository, progress.checkpoint()).await;
+            }
+            Err(e) => {
+                warn!("Failed to connect to Jetstream at {}: {}", endpoint, e);
+            }
+        }
+
+        attempt = attempt.saturating_add(1);
+        endpoint_index += 1;
+
+        let delay = backoff_delay(&settings, attempt);
+        state.send_replace(ConnectionState::Reconnecting {
+            attempt,
+            retry_in_ms: delay.as_millis() as u64,
+        });
+        info!("Reconnecting to Jetstream in {:?} (attempt {})", delay, attempt);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => {}
+        }
+    }
+
+    state.send_replace(ConnectionState::Disconnected);
+    if let Some(reconciler) = reconciler {
+        // finishes the bootstrap it is working on, if any
+        reconciler.await.ok();
+    }
+    drain(&settings, repository, progress).await
+}
+
+/// Events dispatched per worker before the stream waits for the oldest one to finish.
+const PENDING_EVENTS_PER_WORKER: usize = 100;
+
+/// The events dispatched so far. Their tasks finish out of order, so the cursor is only
+/// checkpointed up to the oldest event still in flight: a crash never skips an unfinished event.
+///
+/// At most `capacity` events are tracked: once full the stream waits for the oldest one, so a
+/// slow database or a hot user can't pile up spawned tasks.
+struct Progress {
+    /// Tasks of the dispatched events with their `time_us`, in dispatch order.
+    in_flight: VecDeque<(u64, JoinHandle<()>)>,
+    last_time_us: Option<u64>,
+    /// The oldest event whose task panicked, e.g. on a database error: the cursor stays before
+    /// it, so it is replayed on the next start.
+    failed_time_us: Option<u64>,
+    capacity: usize,
+}
+
+impl Progress {
+    /// Continues from the persisted cursor, if any.
+    fn resume(last_time_us: Option<u64>, capacity: usize) -> Self {
+        Progress {
+            in_flight: VecDeque::new(),
+            last_time_us,
+            failed_time_us: None,
+            capacity,
+        }
+    }
+
+    fn dispatched(&mut self, time_us: u64, task: Option<JoinHandle<()>>) {
+        if let Some(task) = task {
+            self.in_flight.push_back((time_us, task));
+        }
+        self.last_time_us = Some(time_us);
+    }
+
+    fn is_full(&mut self) -> bool {
+        self.pop_finished();
+        self.in_flight.len() >= self.capacity
+    }
+
+    /// Waits for the oldest in-flight task, making room for the next event.
+    async fn oldest_finished(&mut self) {
+        if let Some((_, task)) = self.in_flight.front_mut() {
+            let result = task.await;
+            self.pop_front(result);
+        }
+        self.pop_finished();
+    }
+
+    /// Forgets the finished tasks at the front; the ones behind an unfinished task are kept
+    /// until it finished, so the checkpoint never passes it.
+    fn pop_finished(&mut self) {
+        while let Some((_, task)) = self.in_flight.front_mut() {
+            let Some(result) = task.now_or_never() else {
+                break;
+            };
+            self.pop_front(result);
+        }
+    }
+
+    fn pop_front(&mut self, result: Result<(), JoinError>) {
+        let Some((time_us, _)) = self.in_flight.pop_front() else {
+            return;
+        };
+        if let Err(e) = result {
+            warn!("Event at {} failed ({}), the cursor stays before it", time_us, e);
+            self.failed_time_us.get_or_insert(time_us);
+        }
+    }
+
+    /// The cursor to persist: right before the oldest unfinished or failed event, or the last
+    /// dispatched event once every task finished.
+    fn checkpoint(&mut self) -> Option<u64> {
+        self.pop_finished();
+
+        let oldest = self.failed_time_us.or(self.in_flight.front().map(|(time_us, _)| *time_us));
+        match oldest {
+            Some(time_us) => Some(time_us.saturating_sub(1)),
+            None => self.last_time_us,
+        }
+    }
+
+    /// Waits for every dispatched task, then returns the checkpoint. The unfinished tasks are
+    /// kept if this is cancelled.
+    async fn finish(&mut self) -> Option<u64> {
+        while let Some((_, task)) = self.in_flight.front_mut() {
+            let result = task.await;
+            self.pop_front(result);
+        }
+
+        self.checkpoint()
+    }
+}
+
+/// Waits for every in-flight event task, then persists the final cursor. The cursor is left
+/// at its last checkpoint if the tasks don't finish in time, so the unfinished events are
+/// replayed on the next start.
+async fn drain(
+    settings: &AppSettings,
+    repository: &Arc<DatabaseRepository>,
+    mut progress: Progress,
+) -> bool {
+    info!("Waiting for in-flight events to finish");
+
+    let timeout = Duration::from_secs(settings.shutdown_timeout_seconds);
+
+    match tokio::time::timeout(timeout, progress.finish()).await {
+        Ok(last_time_us) => {
+            save_cursor(settings, repository, last_time_us).await;
+            info!("Jetstream listener stopped");
+            true
+        }
+        Err(_) => {
+            warn!(
+                "{} event tasks still running after {:?}, not advancing the cursor",
+                progress.in_flight.len(),
+                timeout
+            );
+            false
+        }
+    }
+}
+
+async fn save_cursor(
+    settings: &AppSettings,
+    repository: &Arc<DatabaseRepository>,
+    last_time_us: Option<u64>,
+) {
+    if let Some(time_us) = last_time_us {
+        repository
+            .cursor
+            .save_cursor(settings.instance_id.clone(), time_us)
+            .await;
+    }
+}
+
+/// An open Jetstream connection. Dropping it stops the task reading the websocket, which
+/// doesn't notice a closed socket by itself.
+struct Connection {
+    receiver: JetstreamReceiver,
+    websocket: AbortHandle,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.websocket.abort();
+    }
+}
+
+async fn connect(
+    settings: &AppSettings,
+    endpoint: String,
+    cursor: Option<DateTime<Utc>>,
+) -> anyhow::Result<Connection> {
     let config = JetstreamConfig {
-        endpoint: DefaultJetstreamEndpoints::USEastTwo.into(),
-        wanted_collections: settings
-            .bsky_topics
-            .iter()
-            .map(|s| Nsid::new(s.to_string()).expect("Failed to create NSID"))
-            .collect(),
-        wanted_dids: settings
-            .bsky_dids
-            .as_ref()
-            .map(|dids| dids.iter().map(|s| Did::new(s.to_string()).expect("Failed to create DID")).collect())
-            .unwrap_or_default(),
+        endpoint,
+        wanted_collections: settings.bsky_topics.clone(),
+        // every user when empty
+        wanted_dids: settings.bsky_dids.clone(),
         compression: JetstreamCompression::Zstd,
-        cursor: None,
+        cursor,
     };
 
-    let (receiver, _) = JetstreamConnector::new(config)
-        .expect("Failed to create Jetstream connector")
-        .connect()
-        .await
-        .expect("Failed to connect to Jetstream");
+    let (receiver, websocket) = JetstreamConnector::new(config)?.connect().await?;
+
+    Ok(Connection {
+        receiver,
+        websocket: websocket.abort_handle(),
+    })
+}
 
+/// Consumes events until the connection drops or goes silent, or shutdown is requested,
+/// checkpointing the cursor as it goes.
+async fn consume(
+    settings: &AppSettings,
+    repository: &Arc<DatabaseRepository>,
+    connection: Connection,
+    dispatcher: &Dispatcher,
+    progress: &mut Progress,
+    shutdown: &mut watch::Receiver<bool>,
+) {
     info!("Starting Jetstream listener");
 
-    let semaphore = Arc::new(Semaphore::new(settings.max_workers));
+    let checkpoint_interval = Duration::from_secs(settings.cursor_checkpoint_seconds);
+    let idle_timeout = Duration::from_secs(settings.jetstream_idle_timeout_seconds);
+    let mut last_checkpoint = Instant::now();
+
+    loop {
+        if progress.is_full() {
+            tokio::select! {
+                _ = progress.oldest_finished() => continue,
+                _ = shutdown.changed() => break,
+            }
+        }
+
+        let event = tokio::select! {
+            event = connection.receiver.recv_async() => event,
+            // also while no events arrive: a recent checkpoint tells `backfill` that ingestion runs
+            _ = tokio::time::sleep_until(last_checkpoint + checkpoint_interval) => {
+                save_cursor(settings, repository, progress.checkpoint()).await;
+                last_checkpoint = Instant::now();
+                continue;
+            }
+            _ = silence(idle_timeout) => {
+                warn!("No event from Jetstream for {:?}", idle_timeout);
+                break;
+            }
+            _ = shutdown.changed() => break,
+        };
+        let Ok(event) = event else {
+            break;
+        };
 
-    while let Ok(event) = receiver.recv_async().await {
         if let Commit(commit) = event {
-            events_handler(repository, commit, Arc::clone(&semaphore)).await;
+            let time_us = commit_time_us(&commit);
+            let task = events_handler(
+                repository,
+                commit,
+                &dispatcher.rules,
+                &dispatcher.locks,
+                Arc::clone(&dispatcher.semaphore),
+            );
+            progress.dispatched(time_us, task);
         }
     }
 }
+
+/// Resolves once `timeout` elapsed, or never if it is zero.
+async fn silence(timeout: Duration) {
+    if timeout.is_zero() {
+        std::future::pending().await
+    } else {
+        tokio::time::sleep(timeout).await
+    }
+}
+
+/// All official Jetstream endpoints followed by any custom ones from the settings.
+fn jetstream_endpoints(settings: &AppSettings) -> Vec<String> {
+    let mut endpoints: Vec<String> = vec![
+        DefaultJetstreamEndpoints::USEastOne.into(),
+        DefaultJetstreamEndpoints::USEastTwo.into(),
+        DefaultJetstreamEndpoints::USWestOne.into(),
+        DefaultJetstreamEndpoints::USWestTwo.into(),
+    ];
+    endpoints.extend(
+        settings
+            .jetstream_endpoints
+            .iter()
+            .filter(|endpoint| !endpoint.is_empty())
+            .cloned(),
+    );
+
+    endpoints
+}
+
+/// Rewinds the checkpointed cursor by the configured window, so a stream that resumes slightly
+/// out of order doesn't skip anything. Replayed events are applied only once, see
+/// [`crate::events::create::grant_experience`].
+fn rewind_cursor(settings: &AppSettings, last_time_us: Option<u64>) -> Option<DateTime<Utc>> {
+    let rewind_us = settings.cursor_rewind_seconds as i64 * 1_000_000;
+    let cursor = DateTime::from_timestamp_micros((last_time_us? as i64).saturating_sub(rewind_us))?;
+
+    info!(
+        "Resuming Jetstream for instance {} from cursor {}",
+        settings.instance_id, cursor
+    );
+
+    Some(cursor)
+}
+
+/// Exponential backoff: `base * 2^(attempt - 1)`, capped at the configured maximum.
+fn backoff_delay(settings: &AppSettings, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let delay = settings.reconnect_base_delay_ms.saturating_mul(1 << exponent);
+
+    Duration::from_millis(delay).min(Duration::from_secs(settings.reconnect_max_delay_seconds))
+}
+
+fn commit_time_us(commit: &CommitEvent) -> u64 {
+    match commit {
+        CommitEvent::Create { info, .. }
+        | CommitEvent::Update { info, .. }
+        | CommitEvent::Delete { info, .. } => info.time_us,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::profile_repository::StalledProfileRepository;
+    use atrium_api::types::string::Nsid;
+    use jetstream_oxide::events::JetstreamEvent;
+    use serde_json::json;
+    use tokio::sync::oneshot;
+
+    fn settings() -> AppSettings {
+        AppSettings {
+            bsky_topics: vec![Nsid::new("app.bsky.feed.post".to_string()).unwrap()],
+            bsky_dids: Vec::new(),
+            max_workers: 1,
+            instance_id: "test".to_string(),
+            cursor_rewind_seconds: 10,
+            cursor_checkpoint_seconds: 3600,
+            jetstream_endpoints: Vec::new(),
+            reconnect_base_delay_ms: 500,
+            reconnect_max_delay_seconds: 60,
+            jetstream_idle_timeout_seconds: 60,
+            bootstrap_reconcile_interval_seconds: 0,
+            shutdown_timeout_seconds: 30,
+        }
+    }
+
+    fn post(did: &str, time_us: u64) -> JetstreamEvent {
+        serde_json::from_value(json!({
+            "did": did,
+            "time_us": time_us,
+            "kind": "commit",
+            "commit": {
+                "operation": "create",
+                "rev": "3lfa4ptq3lk2c",
+                "rkey": "3lfa4ptq3lk2c",
+                "collection": "app.bsky.feed.post",
+                "cid": "bafyreig2fjxi3rptqdgylg7e5hmjl6mcke7rn2b6cugzlqq3i4zu6rq52q",
+                "record": {
+                    "$type": "app.bsky.feed.post",
+                    "text": "hello world",
+                    "createdAt": "2025-01-01T00:00:00.000Z",
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cursor_stops_before_the_oldest_unfinished_event() {
+        let mut progress = Progress::resume(Some(100), 10);
+        let (release, released) = oneshot::channel::<()>();
+
+        progress.dispatched(200, Some(tokio::spawn(async move {
+            released.await.ok();
+        })));
+        progress.dispatched(300, Some(tokio::spawn(async {})));
+        progress.dispatched(400, None);
+
+        assert_eq!(progress.checkpoint(), Some(199));
+
+        release.send(()).unwrap();
+        assert_eq!(progress.finish().await, Some(400));
+        assert_eq!(progress.checkpoint(), Some(400));
+    }
+
+    #[tokio::test]
+    async fn cursor_stays_before_a_failed_event() {
+        let mut progress = Progress::resume(None, 10);
+
+        progress.dispatched(200, Some(tokio::spawn(async {})));
+        progress.dispatched(300, Some(tokio::spawn(async {
+            panic!("Failed to insert event");
+        })));
+        progress.dispatched(400, Some(tokio::spawn(async {})));
+
+        assert_eq!(progress.finish().await, Some(299));
+        progress.dispatched(500, Some(tokio::spawn(async {})));
+        assert_eq!(progress.finish().await, Some(299));
+    }
+
+    /// Consumes `receiver` then drains, like [`start_jetstream`] does, with the in-memory
+    /// repository and an AppView that doesn't answer until released.
+    struct Listener {
+        repository: Arc<DatabaseRepository>,
+        profiles: Arc<StalledProfileRepository>,
+        semaphore: Arc<Semaphore>,
+        shutdown: watch::Sender<bool>,
+        task: JoinHandle<bool>,
+    }
+
+    /// A connection to `receiver`, with a websocket task that runs until aborted.
+    fn connection(receiver: JetstreamReceiver) -> (Connection, JoinHandle<()>) {
+        let websocket = tokio::spawn(std::future::pending());
+        let connection = Connection {
+            receiver,
+            websocket: websocket.abort_handle(),
+        };
+
+        (connection, websocket)
+    }
+
+    fn listen(connection: Connection, capacity: usize) -> Listener {
+        listen_with(settings(), connection, capacity)
+    }
+
+    fn listen_with(settings: AppSettings, connection: Connection, capacity: usize) -> Listener {
+        let profiles = Arc::new(StalledProfileRepository::new());
+        let mut repository = DatabaseRepository::in_memory();
+        repository.profiles = Arc::clone(&profiles) as _;
+        let repository = Arc::new(repository);
+        let dispatcher = Dispatcher {
+            rules: Arc::new(RulesStore::load(None).unwrap()),
+            locks: Arc::new(UserLocks::new()),
+            semaphore: Arc::new(Semaphore::new(settings.max_workers)),
+        };
+        let semaphore = Arc::clone(&dispatcher.semaphore);
+        let (shutdown, mut shutdown_receiver) = watch::channel(false);
+
+        let task = tokio::spawn({
+            let repository = Arc::clone(&repository);
+            async move {
+                let mut progress = Progress::resume(None, capacity);
+                consume(
+                    &settings,
+                    &repository,
+                    connection,
+                    &dispatcher,
+                    &mut progress,
+                    &mut shutdown_receiver,
+                )
+                .await;
+                drain(&settings, &repository, progress).await
+            }
+        });
+
+        Listener {
+            repository,
+            profiles,
+            semaphore,
+            shutdown,
+            task,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shutdown_saves_the_cursor_once_the_in_flight_events_finished() {
+        let (events, receiver) = flume::unbounded();
+        // a newcomer's post waits for their profile while holding the only worker
+        events.send(post("did:plc:stalled", 1_000)).unwrap();
+        let listener = listen(connection(receiver).0, 10);
+        while listener.semaphore.available_permits() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        listener.shutdown.send_replace(true);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!listener.task.is_finished());
+        let cursor = &listener.repository.cursor;
+        assert!(cursor.find_cursor("test".to_string()).await.is_none());
+
+        listener.profiles.release();
+        assert!(listener.task.await.unwrap());
+        assert_eq!(listener.semaphore.available_permits(), 1);
+        let cursor = cursor.find_cursor("test".to_string()).await.unwrap();
+        assert_eq!(cursor.time_us, 1_000);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn the_stream_waits_once_too_many_events_are_in_flight() {
+        let (events, receiver) = flume::unbounded();
+        for event in 1..=5 {
+            events.send(post(&format!("did:plc:stalled{}", event), event)).unwrap();
+        }
+        let listener = listen(connection(receiver).0, 2);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(events.len(), 3);
+
+        listener.profiles.release();
+        while !events.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        listener.shutdown.send_replace(true);
+        assert!(listener.task.await.unwrap());
+        let cursor = listener.repository.cursor.find_cursor("test".to_string()).await.unwrap();
+        assert_eq!(cursor.time_us, 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_silent_connection_counts_as_lost() {
+        let (_events, receiver) = flume::unbounded();
+        let (connection, websocket) = connection(receiver);
+        let listener = listen(connection, 10);
+
+        // stops consuming on its own, so the supervisor reconnects
+        assert!(listener.task.await.unwrap());
+        assert!(websocket.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_cursor_is_saved_while_no_events_arrive() {
+        let (events, receiver) = flume::unbounded();
+        events.send(post("did:plc:stalled", 1_000)).unwrap();
+        let settings = AppSettings {
+            cursor_checkpoint_seconds: 5,
+            ..settings()
+        };
+        let listener = listen_with(settings, connection(receiver).0, 10);
+        let cursor = &listener.repository.cursor;
+
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        assert!(cursor.find_cursor("test".to_string()).await.is_none());
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let cursor = cursor.find_cursor("test".to_string()).await.unwrap();
+        assert_eq!(cursor.time_us, 999);
+    }
+}
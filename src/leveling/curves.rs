@@ -0,0 +1,308 @@
+use crate::leveling::{BASE_EXPERIENCE, EXPERIENCE_PER_LEVEL, LEVEL_CAP};
+use anyhow::bail;
+use serde::Deserialize;
+
+/// Maps levels to the total XP needed to reach them, and back.
+///
+/// Implementations only need to provide `xp_for_level`; `level_for_xp` defaults to a binary
+/// search over `1..=LEVEL_CAP`, which is the inverse for any non-decreasing curve:
+///     xp_for_level(n) <= xp < xp_for_level(n+1)  =>  level_for_xp(xp) == n
+pub trait LevelCurve: Send + Sync {
+    /// Returns the XP required to *reach* `level`. Must be non-decreasing in `level`.
+    fn xp_for_level(&self, level: i32) -> i64;
+
+    /// Given a total XP value, compute which level you're on (at least 1, at most `LEVEL_CAP`).
+    fn level_for_xp(&self, xp: i64) -> i32 {
+        let (mut low, mut high) = (1, LEVEL_CAP);
+
+        // find the highest level whose threshold is already reached
+        while low < high {
+            let mid = low + (high - low + 1) / 2;
+            if self.xp_for_level(mid) <= xp {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        low
+    }
+}
+
+/// Converts a real-valued threshold to XP, saturating instead of wrapping.
+fn saturate(xp: f64) -> i64 {
+    if xp.is_nan() {
+        0
+    } else {
+        // `as` saturates at i64::MAX for values past it
+        xp.ceil().max(0.0) as i64
+    }
+}
+
+/// Arithmetic progression:
+///     xp_for_level(1) = base
+///     xp_for_level(n) = base + (n-1)*step
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinearCurve {
+    pub base: i64,
+    pub step: i64,
+}
+
+impl Default for LinearCurve {
+    fn default() -> Self {
+        Self {
+            base: BASE_EXPERIENCE,
+            step: EXPERIENCE_PER_LEVEL,
+        }
+    }
+}
+
+impl LevelCurve for LinearCurve {
+    fn xp_for_level(&self, level: i32) -> i64 {
+        if level <= 1 {
+            self.base
+        } else {
+            self.base.saturating_add((level as i64 - 1).saturating_mul(self.step))
+        }
+    }
+
+    fn level_for_xp(&self, xp: i64) -> i32 {
+        // If you have less XP than `base`, you haven't fully
+        // reached level 1 yet—but let's call it level 1 for convenience.
+        if xp < self.base {
+            return 1;
+        }
+
+        // Solve for n in:
+        //   xp >= base + (n-1)*step
+        //   => n <= 1 + ((xp - base) / step)
+        //
+        // We take the integer floor of that expression.
+        (1 + (xp - self.base) / self.step).min(LEVEL_CAP as i64) as i32
+    }
+}
+
+/// Geometric progression:
+///     xp_for_level(n) = base * factor^(n-1)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExponentialCurve {
+    pub base: i64,
+    pub factor: f64,
+}
+
+impl LevelCurve for ExponentialCurve {
+    fn xp_for_level(&self, level: i32) -> i64 {
+        saturate(self.base as f64 * self.factor.powi(level.max(1) - 1))
+    }
+}
+
+/// Logarithmic progression, levels come faster and faster:
+///     xp_for_level(n) = base + scale * ln(n)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogarithmicCurve {
+    pub base: i64,
+    pub scale: f64,
+}
+
+impl LevelCurve for LogarithmicCurve {
+    fn xp_for_level(&self, level: i32) -> i64 {
+        saturate(self.base as f64 + self.scale * (level.max(1) as f64).ln())
+    }
+}
+
+/// Polynomial progression:
+///     xp_for_level(n) = base + coefficient * (n-1)^exponent
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolynomialCurve {
+    pub base: i64,
+    pub coefficient: f64,
+    pub exponent: f64,
+}
+
+impl LevelCurve for PolynomialCurve {
+    fn xp_for_level(&self, level: i32) -> i64 {
+        let n = (level.max(1) - 1) as f64;
+        saturate(self.base as f64 + self.coefficient * n.powf(self.exponent))
+    }
+}
+
+/// Explicit thresholds: `thresholds[n-1]` is the XP required to reach level `n`.
+/// Levels past the end of the table can't be reached.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableCurve {
+    pub thresholds: Vec<i64>,
+}
+
+impl LevelCurve for TableCurve {
+    fn xp_for_level(&self, level: i32) -> i64 {
+        let index = (level.max(1) - 1) as usize;
+        self.thresholds.get(index).copied().unwrap_or(i64::MAX)
+    }
+
+    fn level_for_xp(&self, xp: i64) -> i32 {
+        let reached = self.thresholds.partition_point(|threshold| *threshold <= xp);
+        reached.clamp(1, LEVEL_CAP as usize) as i32
+    }
+}
+
+/// Curve selection as written in the rules file, e.g.
+///
+/// ```toml
+/// [curve]
+/// type = "exponential"
+/// base = 50
+/// factor = 1.1
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CurveConfig {
+    Linear(LinearCurve),
+    Exponential(ExponentialCurve),
+    Logarithmic(LogarithmicCurve),
+    Polynomial(PolynomialCurve),
+    Table(TableCurve),
+}
+
+impl Default for CurveConfig {
+    fn default() -> Self {
+        CurveConfig::Linear(LinearCurve::default())
+    }
+}
+
+impl CurveConfig {
+    pub fn as_curve(&self) -> &dyn LevelCurve {
+        match self {
+            CurveConfig::Linear(curve) => curve,
+            CurveConfig::Exponential(curve) => curve,
+            CurveConfig::Logarithmic(curve) => curve,
+            CurveConfig::Polynomial(curve) => curve,
+            CurveConfig::Table(curve) => curve,
+        }
+    }
+
+    /// Rejects parameters that would make the curve decreasing or meaningless.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let base = match self {
+            CurveConfig::Linear(curve) => {
+                if curve.step <= 0 {
+                    bail!("linear curve: step must be greater than zero");
+                }
+                curve.base
+            }
+            CurveConfig::Exponential(curve) => {
+                if !(curve.factor.is_finite() && curve.factor > 1.0) {
+                    bail!("exponential curve: factor must be greater than 1");
+                }
+                curve.base
+            }
+            CurveConfig::Logarithmic(curve) => {
+                if !(curve.scale.is_finite() && curve.scale > 0.0) {
+                    bail!("logarithmic curve: scale must be greater than zero");
+                }
+                curve.base
+            }
+            CurveConfig::Polynomial(curve) => {
+                if !(curve.coefficient.is_finite() && curve.coefficient > 0.0) {
+                    bail!("polynomial curve: coefficient must be greater than zero");
+                }
+                if !(curve.exponent.is_finite() && curve.exponent > 0.0) {
+                    bail!("polynomial curve: exponent must be greater than zero");
+                }
+                curve.base
+            }
+            CurveConfig::Table(curve) => {
+                if curve.thresholds.is_empty() {
+                    bail!("table curve: at least one threshold is required");
+                }
+                if curve.thresholds.windows(2).any(|pair| pair[0] >= pair[1]) {
+                    bail!("table curve: thresholds must be strictly increasing");
+                }
+                curve.thresholds[0]
+            }
+        };
+
+        if base <= 0 {
+            bail!("curve: base experience must be greater than zero");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn curves() -> impl Strategy<Value = CurveConfig> {
+        prop_oneof![
+            (1..1_000i64, 1..1_000i64)
+                .prop_map(|(base, step)| CurveConfig::Linear(LinearCurve { base, step })),
+            (1..1_000i64, 1.001..3.0f64)
+                .prop_map(|(base, factor)| CurveConfig::Exponential(ExponentialCurve { base, factor })),
+            (1..1_000i64, 1.0..100_000.0f64)
+                .prop_map(|(base, scale)| CurveConfig::Logarithmic(LogarithmicCurve { base, scale })),
+            (1..1_000i64, 0.1..100.0f64, 0.5..4.0f64).prop_map(|(base, coefficient, exponent)| {
+                CurveConfig::Polynomial(PolynomialCurve {
+                    base,
+                    coefficient,
+                    exponent,
+                })
+            }),
+            prop::collection::btree_set(1..i64::MAX, 1..50).prop_map(|thresholds| {
+                CurveConfig::Table(TableCurve {
+                    thresholds: thresholds.into_iter().collect(),
+                })
+            }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn generated_curves_are_valid(config in curves()) {
+            prop_assert!(config.validate().is_ok());
+        }
+
+        #[test]
+        fn xp_for_level_is_monotonic(config in curves(), level in 1..LEVEL_CAP) {
+            let curve = config.as_curve();
+            prop_assert!(curve.xp_for_level(level) <= curve.xp_for_level(level + 1));
+        }
+
+        #[test]
+        fn level_for_xp_is_monotonic(config in curves(), xp in 0..i64::MAX - 1) {
+            let curve = config.as_curve();
+            prop_assert!(curve.level_for_xp(xp) <= curve.level_for_xp(xp + 1));
+        }
+
+        #[test]
+        fn level_for_xp_is_inverse_of_xp_for_level(config in curves(), xp in 0..i64::MAX) {
+            let curve = config.as_curve();
+            let level = curve.level_for_xp(xp);
+
+            prop_assert!((1..=LEVEL_CAP).contains(&level));
+            if level > 1 {
+                prop_assert!(curve.xp_for_level(level) <= xp);
+            }
+            if level < LEVEL_CAP {
+                prop_assert!(xp < curve.xp_for_level(level + 1));
+            }
+        }
+
+        #[test]
+        fn xp_for_level_round_trips(config in curves(), level in 1..LEVEL_CAP) {
+            let curve = config.as_curve();
+            let xp = curve.xp_for_level(level);
+
+            // every level with its own threshold is reached exactly at that threshold
+            if xp < curve.xp_for_level(level + 1) && (level == 1 || curve.xp_for_level(level - 1) < xp) {
+                prop_assert_eq!(curve.level_for_xp(xp), level);
+            }
+        }
+    }
+}
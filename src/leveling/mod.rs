@@ -0,0 +1,181 @@
+pub mod curves;
+
+use crate::leveling::curves::LevelCurve;
+use atrium_api::app::bsky::actor::defs::ProfileViewDetailed;
+
+/// The maximum level a user can attain.
+pub const LEVEL_CAP: i32 = 100000;
+
+/// How many XP are required for each level after the base (default linear curve).
+pub const EXPERIENCE_PER_LEVEL: i64 = 100;
+
+/// How many XP you need to reach Level 1 (default linear curve).
+pub const BASE_EXPERIENCE: i64 = 50;
+
+/// A response struct that includes additional metadata about leveling.
+#[derive(Debug, Default, Clone)]
+pub struct LevelResponse {
+    /// The new total level (clamped at LEVEL_CAP).
+    pub level: i32,
+    /// The user's total, accumulated experience points. Keeps accumulating past `LEVEL_CAP`.
+    pub experience: i64,
+    /// The XP required to reach the *next* level. Zero if already at or above `LEVEL_CAP`.
+    pub experience_to_next_level: i64,
+    /// How many levels the user gained in this single experience increment.
+    /// Negative when experience was revoked and the user leveled down.
+    pub _levels_gained: i32,
+    /// A float (0.0 ..= 1.0) representing how far the user is from `level` to `level + 1`.
+    /// If `level` = `LEVEL_CAP`, this can safely be 1.0 (or 0.0, depending on your preference).
+    pub _progress_percentage: f32,
+}
+
+/// Calculate new level/XP state, given the current XP and newly gained XP.
+/// The XP thresholds come from the configured [`LevelCurve`].
+///
+/// # Examples
+/// ```
+/// let resp = calculate_experience(&LinearCurve::default(), 0, 30);
+/// // resp.level == 1
+/// // resp.experience == 30
+/// // resp.experience_to_next_level == 20  (need total of 50 to hit level 1 threshold)
+/// // resp.levels_gained == 0
+/// // resp.progress_percentage == 0.6  (30 / 50)
+/// ```
+pub fn calculate_experience(
+    curve: &dyn LevelCurve,
+    current_experience: i64,
+    new_experience: i64,
+) -> LevelResponse {
+    // Negative totals can only come from corrupted counters; treat them as no experience.
+    let current_experience = current_experience.max(0);
+    let new_experience = new_experience.max(0);
+
+    // Figure out old level vs. new level to track "levels gained."
+    let old_level = curve.level_for_xp(current_experience);
+    let new_level = curve.level_for_xp(new_experience);
+
+    let level = new_level.min(LEVEL_CAP);
+
+    // If we're at or above the level cap, no more progress to be made.
+    let (experience_to_next_level, progress_percentage) = if level >= LEVEL_CAP {
+        (0, 1.0_f32)
+    } else {
+        // The XP needed to *reach* the next level.
+        let next_level_xp = curve.xp_for_level(level + 1);
+
+        // For progress percentage, we see:
+        //  current_level_xp = xp_for_level(level)
+        //  next_level_xp = xp_for_level(level + 1)
+        //  range = next_level_xp - current_level_xp
+        //  progress = (total_experience - current_level_xp) / range
+        let current_level_xp = curve.xp_for_level(level);
+        let range = next_level_xp.saturating_sub(current_level_xp).max(1);
+        let progress = (new_experience.saturating_sub(current_level_xp)) as f32 / range as f32;
+
+        (next_level_xp, progress)
+    };
+
+    // How many levels were gained (or lost, on revocation) in this single increment?
+    let _levels_gained = new_level - old_level;
+
+    LevelResponse {
+        level,
+        experience: new_experience,
+        experience_to_next_level,
+        _levels_gained,
+        _progress_percentage: progress_percentage,
+    }
+}
+
+/// Calculate the base level of a user based on their Bsky profile
+///
+/// # Arguments
+///
+/// * `profile`: &ProfileViewDetailed - The profile to calculate the base level from.
+/// * `post_experience`: i64 - The base experience granted per post.
+/// * `curve`: &dyn LevelCurve - The curve used to turn experience into a level.
+///
+/// returns: LevelResponse
+pub fn get_base_level_from_bsky_profile(
+    profile: &ProfileViewDetailed,
+    post_experience: i64,
+    curve: &dyn LevelCurve,
+) -> LevelResponse {
+    // TODO: implement a way to list all likes sent by an account.
+    // profiles without counts (e.g. some takedowns) start from scratch
+    let experience = profile
+        .posts_count
+        .unwrap_or_default()
+        .max(0)
+        .saturating_mul(post_experience);
+
+    calculate_experience(curve, 0, experience)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leveling::curves::{ExponentialCurve, LinearCurve};
+
+    #[test]
+    fn experience_beyond_i32_is_kept() {
+        let experience = i32::MAX as i64 * 4;
+        let response = calculate_experience(&LinearCurve::default(), 0, experience);
+
+        assert_eq!(response.experience, experience);
+        assert_eq!(response.level, LEVEL_CAP);
+    }
+
+    #[test]
+    fn level_saturates_at_cap() {
+        let response = calculate_experience(&LinearCurve::default(), i64::MAX - 1, i64::MAX);
+
+        assert_eq!(response.level, LEVEL_CAP);
+        assert_eq!(response.experience, i64::MAX);
+        assert_eq!(response.experience_to_next_level, 0);
+        assert_eq!(response._levels_gained, 0);
+        assert_eq!(response._progress_percentage, 1.0);
+    }
+
+    #[test]
+    fn reaching_the_cap_counts_levels_gained() {
+        let curve = LinearCurve::default();
+        let cap_experience = curve.xp_for_level(LEVEL_CAP);
+        let response = calculate_experience(&curve, 0, cap_experience);
+
+        assert_eq!(response.level, LEVEL_CAP);
+        assert_eq!(response._levels_gained, LEVEL_CAP - 1);
+    }
+
+    #[test]
+    fn just_below_the_cap() {
+        let curve = LinearCurve::default();
+        let experience = curve.xp_for_level(LEVEL_CAP) - 1;
+        let response = calculate_experience(&curve, experience, experience);
+
+        assert_eq!(response.level, LEVEL_CAP - 1);
+        assert_eq!(response.experience_to_next_level, curve.xp_for_level(LEVEL_CAP));
+    }
+
+    #[test]
+    fn exponential_curve_saturates_instead_of_wrapping() {
+        let curve = ExponentialCurve {
+            base: 50,
+            factor: 10.0,
+        };
+
+        assert_eq!(curve.xp_for_level(LEVEL_CAP), i64::MAX);
+        assert!(curve.xp_for_level(LEVEL_CAP - 1) <= curve.xp_for_level(LEVEL_CAP));
+
+        let response = calculate_experience(&curve, 0, i64::MAX);
+        assert_eq!(response.level, LEVEL_CAP);
+    }
+
+    #[test]
+    fn negative_experience_is_treated_as_zero() {
+        let response = calculate_experience(&LinearCurve::default(), i64::MIN, -1);
+
+        assert_eq!(response.experience, 0);
+        assert_eq!(response.level, 1);
+    }
+}
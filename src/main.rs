@@ -1,83 +1,201 @@
 //! A very basic example of how to listen for create/delete events on a specific DID and NSID.
 
+mod args;
+mod backfill;
+mod database;
 mod events;
 mod http;
+mod inspect;
 mod jetstream;
 mod leveling;
+mod migrate;
 mod models;
+mod repair;
 mod repositories;
-mod args;
+mod reconcile;
+mod rules;
 
-use scylla::{CachingSession, SessionBuilder};
+use scylla::CachingSession;
 
+use crate::args::{AppSettings, Cli, Command, HttpSettings, RunSettings};
 use crate::http::start_http;
-use crate::jetstream::start_jetstream;
-use actix_web::rt::signal;
-use scylla::transport::session::{CurrentDeserializationApi, GenericSession};
+use crate::jetstream::{start_jetstream, ConnectionState};
+use crate::repositories::{DatabaseRepository, OperationConsistency};
+use paris::Logger;
+use rules::RulesStore;
 use std::sync::Arc;
+use tokio::signal::unix::{signal as unix_signal, SignalKind};
+use tokio::sync::watch;
 use tokio::task::JoinSet;
-use args::AppSettings;
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 10)]
 async fn main() {
-    
-    let settings = Arc::new(AppSettings::new());
-    let session = start_scylla_session().await;
-    let caching_session = Arc::new(CachingSession::from(session, 50));
+    Logger::new();
+    env_logger::init();
+
+    let cli = Cli::load();
+    let command = cli.command.unwrap_or(Command::Run(cli.run));
+
+    if let Command::Migrate { migrate } = &command {
+        let up_to_date = migrate::migrate(&cli.database, migrate).await;
+        std::process::exit(if up_to_date { 0 } else { 1 });
+    }
+
+    let session = match database::start_scylla_session(&cli.database).await {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Failed to start the ScyllaDB session: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let caching_session = Arc::new(CachingSession::from(session, cli.database.scylla_cache_size));
+
+    let repository = match DatabaseRepository::new(
+        Arc::clone(&caching_session),
+        OperationConsistency::from(&cli.database),
+        &cli.bsky,
+    ) {
+        Ok(repository) => Arc::new(repository),
+        Err(e) => {
+            eprintln!("Failed to set up the repositories: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let repository = Arc::new(repositories::DatabaseRepository::new(Arc::clone(
-        &caching_session,
-    )));
+    let rules = match RulesStore::load(cli.xp_rules_path) {
+        Ok(rules) => Arc::new(rules),
+        Err(e) => {
+            eprintln!("Failed to load experience rules: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let clean = match command {
+        Command::Run(RunSettings { ingest, http }) => {
+            run_services(repository, rules, Some(ingest), Some(http)).await
+        }
+        Command::Serve { http } => run_services(repository, rules, None, Some(http)).await,
+        Command::Ingest { ingest } => run_services(repository, rules, Some(ingest), None).await,
+        Command::Backfill { did, backfill } => {
+            backfill::run_backfill(&repository, &rules, did, &backfill, &cli.bsky).await
+        }
+        Command::Recompute => {
+            repair::recompute_levels(&repository, &rules.current()).await;
+            true
+        }
+        Command::Inspect { did } => inspect::inspect_character(&repository, did).await,
+        Command::RepairEventTypes => {
+            repair::repair_event_types(&repository).await;
+            true
+        }
+        Command::Migrate { .. } => unreachable!("handled before connecting to the keyspace"),
+    };
+
+    std::process::exit(if clean { 0 } else { 1 });
+}
 
+/// Runs the Jetstream ingestion and/or the HTTP API until a shutdown signal arrives or a
+/// service stops on its own. Returns whether everything shut down cleanly.
+async fn run_services(
+    repository: Arc<DatabaseRepository>,
+    rules: Arc<RulesStore>,
+    ingest: Option<AppSettings>,
+    http: Option<HttpSettings>,
+) -> bool {
+    let (jetstream_state, jetstream_state_receiver) = watch::channel(ConnectionState::Disconnected);
+    let (shutdown, shutdown_receiver) = watch::channel(false);
+
+    // every service reports whether it stopped cleanly
     let mut join = JoinSet::new();
-    let jetstream_repository = Arc::clone(&repository);
-    join.spawn(async move {
-        start_jetstream(settings, &jetstream_repository).await;
-    });
 
-    join.spawn(async move {
-        let _ = start_http(&repository).await;
-    });
+    if let Some(settings) = ingest {
+        let jetstream_repository = Arc::clone(&repository);
+        let jetstream_rules = Arc::clone(&rules);
+        let jetstream_shutdown = shutdown_receiver.clone();
+        join.spawn(async move {
+            start_jetstream(
+                Arc::new(settings),
+                &jetstream_repository,
+                jetstream_rules,
+                jetstream_state,
+                jetstream_shutdown,
+            )
+            .await
+        });
+    }
+
+    if let Some(settings) = http {
+        let http_rules = Arc::clone(&rules);
+        let http_shutdown = shutdown_receiver.clone();
+        join.spawn(async move {
+            match start_http(
+                &settings,
+                &repository,
+                jetstream_state_receiver,
+                http_rules,
+                http_shutdown,
+            )
+            .await
+            {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("HTTP server failed: {}", e);
+                    false
+                }
+            }
+        });
+    }
 
-    // Listen for Ctrl+C (SIGINT) or termination signals (SIGTERM)
-    let signal_handle = tokio::spawn(async {
-        signal::ctrl_c().await.expect("Failed to listen for Ctrl+C");
-        println!("Ctrl+C pressed. Shutting down...");
+    // Reload the experience rules on SIGHUP
+    let mut hangup_shutdown = shutdown_receiver;
+    join.spawn(async move {
+        let mut hangup = unix_signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => rules.reload(),
+                _ = hangup_shutdown.wait_for(|shutdown| *shutdown) => return true,
+            }
+        }
     });
 
-    // Wait for tasks or signals
-    tokio::select! {
-        _ = signal_handle => {
-            println!("Received shutdown signal.");
+    // Run until a shutdown signal arrives or a service stops on its own
+    let mut clean = tokio::select! {
+        _ = shutdown_signal() => true,
+        Some(task) = join.join_next() => {
+            eprintln!("A service stopped unexpectedly: {:?}", task);
+            false
         }
-        _ = async {
-            while let Some(task) = join.join_next().await {
-                if let Err(e) = task {
-                    eprintln!("Task failed: {:?}", e);
-                }
+    };
+
+    // Stop consuming, drain the in-flight events and stop the HTTP server
+    shutdown.send_replace(true);
+    while let Some(task) = join.join_next().await {
+        match task {
+            Ok(stopped_cleanly) => clean &= stopped_cleanly,
+            Err(e) => {
+                eprintln!("Task failed: {:?}", e);
+                clean = false;
             }
-        } => {
-            println!("All tasks completed or terminated.");
         }
     }
 
-    println!("Connection to Jetstream lost. Application shutting down.");
+    if clean {
+        println!("Application shut down cleanly.");
+    } else {
+        eprintln!("Application shut down with errors.");
+    }
+
+    clean
 }
 
-async fn start_scylla_session() -> GenericSession<CurrentDeserializationApi> {
-    let session = SessionBuilder::new()
-        .known_nodes(vec![
-            "localhost:19042",
-            "localhost:19043",
-            "localhost:19044",
-        ])
-        .build()
-        .await
-        .expect("Failed to create Scylla session");
-
-    session
-        .use_keyspace("bsky_rpg", false)
-        .await
-        .expect("Failed to use keyspace");
-    session
+/// Resolves on Ctrl+C (SIGINT) or a termination request (SIGTERM).
+async fn shutdown_signal() {
+    let mut interrupt = unix_signal(SignalKind::interrupt()).expect("Failed to listen for SIGINT");
+    let mut terminate = unix_signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+
+    tokio::select! {
+        _ = interrupt.recv() => println!("Received SIGINT. Shutting down..."),
+        _ = terminate.recv() => println!("Received SIGTERM. Shutting down..."),
+    }
 }
@@ -0,0 +1,679 @@
+use crate::args::{DatabaseSettings, MigrateSettings, ReplicationStrategy};
+use crate::database;
+use charybdis_parser::schema::code_schema::CodeSchema;
+use charybdis_parser::schema::db_schema::DbSchema;
+use charybdis_parser::schema::{SchemaObject, SchemaObjects};
+use futures::StreamExt;
+use paris::{error, info, warn};
+use scylla::Session;
+use std::collections::BTreeMap;
+
+/// The models, compiled into the binary so migrating doesn't need the source tree.
+const MODEL_SOURCES: [(&str, &str); 8] = [
+    ("character.rs", include_str!("models/character.rs")),
+    ("character_experience.rs", include_str!("models/character_experience.rs")),
+    ("event_record.rs", include_str!("models/event_record.rs")),
+    ("events.rs", include_str!("models/events.rs")),
+    ("ingestion_cursor.rs", include_str!("models/ingestion_cursor.rs")),
+    (
+        "materialized_views/event_history_by_type.rs",
+        include_str!("models/materialized_views/event_history_by_type.rs"),
+    ),
+    ("pending_bootstrap.rs", include_str!("models/pending_bootstrap.rs")),
+    ("udts/leveling.rs", include_str!("models/udts/leveling.rs")),
+];
+
+/// A table of an earlier version, replaced by a model with another primary key (which can't be
+/// altered). Its rows are copied into the new table when that one is created; the old table and
+/// its views are left for the operator to drop.
+struct ReplacedTable {
+    table: &'static str,
+    views: &'static [&'static str],
+    replaced_by: &'static str,
+}
+
+const REPLACED_TABLES: [ReplacedTable; 1] = [
+    // keyed by `event_at` alone, so events sharing a timestamp overwrote each other
+    ReplacedTable {
+        table: "events",
+        views: &["events_by_type"],
+        replaced_by: "event_history",
+    },
+];
+
+/// `(type or table, field)` of fields the models no longer use but that can't be dropped: the
+/// upgrade to 64-bit experience (see the README) renames the `int` fields of the leveling UDT out
+/// of the way, and ScyllaDB can't drop UDT fields.
+const RETIRED_FIELDS: [(&str, &str); 2] = [
+    ("leveling", "experience_int"),
+    ("leveling", "experience_to_next_level_int"),
+];
+
+/// What it takes to bring the live schema in line with the models.
+#[derive(Debug, Default)]
+pub struct MigrationPlan {
+    /// CQL statements that are safe to apply, in order.
+    pub statements: Vec<String>,
+    /// Tables whose rows are copied, as `(from, to)`, once the statements ran.
+    pub copies: Vec<(String, String)>,
+    /// Differences that can't be applied automatically (type or key changes, objects or fields
+    /// that only exist in the database). They are reported and left untouched.
+    pub drift: Vec<String>,
+    /// Objects of earlier versions the models no longer use, safe to drop once migrated.
+    pub retired: Vec<String>,
+}
+
+/// Creates the keyspace if needed and applies the models to it. With `dry_run` the CQL is only
+/// printed. Returns `false` if the schema drifted from the models or a statement failed.
+///
+/// The models are parsed by `charybdis_parser`, like `charybdis-migrate` does, but its runner
+/// isn't used: it reads `src/models` at runtime, panics when a statement fails and has neither
+/// a dry run nor a drift report.
+pub async fn migrate(database: &DatabaseSettings, settings: &MigrateSettings) -> bool {
+    let session = match database::connect(database).await {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Failed to start the ScyllaDB session: {:#}", e);
+            return false;
+        }
+    };
+
+    let keyspace = &database.scylla_keyspace;
+    let keyspace_exists = keyspace_exists(&session, keyspace);
+
+    let mut plan = if keyspace_exists {
+        let db_schema = DbSchema::new(&session, keyspace.clone()).await;
+        plan_migration(keyspace, &db_schema, &code_schema())
+    } else {
+        let empty = DbSchema {
+            tables: SchemaObjects::new(),
+            udts: SchemaObjects::new(),
+            materialized_views: SchemaObjects::new(),
+            keyspace_name: keyspace.clone(),
+        };
+        plan_migration(keyspace, &empty, &code_schema())
+    };
+
+    if !keyspace_exists {
+        plan.statements.insert(0, create_keyspace(keyspace, settings));
+    }
+
+    for drift in &plan.drift {
+        warn!("Schema drift: {}", drift);
+    }
+    for retired in &plan.retired {
+        info!("Retired: {}", retired);
+    }
+
+    if plan.statements.is_empty() {
+        info!("Schema of keyspace {} is up to date", keyspace);
+    } else if settings.dry_run {
+        info!("Dry run, the migration would run:");
+        for statement in &plan.statements {
+            println!("{}\n", statement);
+        }
+        for (from, to) in &plan.copies {
+            println!("-- then copy every row of {keyspace}.{from} into {keyspace}.{to}\n");
+        }
+    } else {
+        for statement in &plan.statements {
+            info!("Running: {}", statement);
+            if let Err(e) = session.query_unpaged(statement.as_str(), ()).await {
+                error!("Migration failed: {}", e);
+                return false;
+            }
+        }
+
+        if let Err(e) = session.await_schema_agreement().await {
+            error!("Failed to reach schema agreement: {}", e);
+            return false;
+        }
+
+        for (from, to) in &plan.copies {
+            info!("Copying the rows of {} into {}", from, to);
+            match copy_rows(&session, keyspace, from, to).await {
+                Ok(copied) => info!("Copied {} rows of {} into {}", copied, from, to),
+                Err(e) => {
+                    error!(
+                        "Failed to copy the rows of {} into {}: {:#}. Drop {} and run the migration again",
+                        from, to, e, to
+                    );
+                    return false;
+                }
+            }
+        }
+        info!("Migration of keyspace {} finished", keyspace);
+    }
+
+    if let Some(path) = &settings.schema_json {
+        if !settings.dry_run {
+            DbSchema::new(&session, keyspace.clone())
+                .await
+                .write_schema_to_json(&path.display().to_string());
+        }
+    }
+
+    plan.drift.is_empty()
+}
+
+/// Parses the embedded models into the schema they describe.
+pub fn code_schema() -> CodeSchema {
+    let mut schema = CodeSchema::default();
+
+    for (file, source) in MODEL_SOURCES {
+        let ast = syn::parse_file(source)
+            .unwrap_or_else(|e| panic!("Failed to parse model {}: {}", file, e));
+
+        schema.populate_udts(&ast);
+        schema.populate_tables(&ast);
+        schema.populate_materialized_views(&ast);
+    }
+
+    schema
+}
+
+/// Diffs the live schema against the models. Objects are created in dependency order:
+/// UDTs, then tables, then the materialized views on top of them.
+pub fn plan_migration(keyspace: &str, db: &DbSchema, code: &CodeSchema) -> MigrationPlan {
+    let mut plan = MigrationPlan::default();
+
+    for (name, udt) in sorted(&code.udts) {
+        match db.udts.get(name) {
+            None => plan.statements.push(format!(
+                "CREATE TYPE IF NOT EXISTS {keyspace}.{name}\n(\n{}\n);",
+                fields_clause(udt)
+            )),
+            Some(existing) => {
+                diff_fields(&mut plan, "TYPE", keyspace, name, existing, udt);
+            }
+        }
+    }
+
+    for (name, table) in sorted(&code.tables) {
+        match db.tables.get(name) {
+            None => {
+                plan.statements.push(format!(
+                    "CREATE TABLE IF NOT EXISTS {keyspace}.{name}\n(\n{},\n    PRIMARY KEY ({})\n){};",
+                    fields_clause(table),
+                    primary_key(table),
+                    options_clause(table)
+                ));
+
+                let replaced = REPLACED_TABLES
+                    .iter()
+                    .filter(|replaced| replaced.replaced_by == name.as_str())
+                    .filter(|replaced| db.tables.contains_key(replaced.table));
+                for replaced in replaced {
+                    plan.copies.push((replaced.table.to_string(), name.clone()));
+                }
+            }
+            Some(existing) => {
+                diff_keys(&mut plan, "table", name, existing, table);
+                diff_fields(&mut plan, "TABLE", keyspace, name, existing, table);
+            }
+        }
+    }
+
+    for (name, view) in sorted(&code.materialized_views) {
+        match db.materialized_views.get(name) {
+            None => {
+                let fields: Vec<&str> = view.fields.iter().map(|(field, _, _)| field.as_str()).collect();
+                let not_null: Vec<String> = view
+                    .partition_keys
+                    .iter()
+                    .chain(&view.clustering_keys)
+                    .map(|key| format!("{key} IS NOT NULL"))
+                    .collect();
+
+                plan.statements.push(format!(
+                    "CREATE MATERIALIZED VIEW IF NOT EXISTS {keyspace}.{name} AS\nSELECT {}\nFROM {keyspace}.{}\nWHERE {}\nPRIMARY KEY ({}){};",
+                    fields.join(", "),
+                    view.base_table,
+                    not_null.join("\n  AND "),
+                    primary_key(view),
+                    options_clause(view)
+                ));
+            }
+            Some(existing) => {
+                // views can't be altered, only dropped and re-created
+                diff_keys(&mut plan, "materialized view", name, existing, view);
+                if field_names(existing) != field_names(view) {
+                    plan.drift.push(format!(
+                        "materialized view {name} selects different fields than the model"
+                    ));
+                }
+            }
+        }
+    }
+
+    for name in db.udts.keys().filter(|name| !code.udts.contains_key(*name)) {
+        plan.drift.push(format!("type {name} exists but has no model"));
+    }
+    for name in db.tables.keys().filter(|name| !code.tables.contains_key(*name)) {
+        match REPLACED_TABLES.iter().find(|replaced| replaced.table == name.as_str()) {
+            Some(replaced) => plan.retired.push(drop_replaced(keyspace, db, replaced)),
+            None => plan.drift.push(format!("table {name} exists but has no model")),
+        }
+    }
+    let retired_views: Vec<&str> = REPLACED_TABLES
+        .iter()
+        .flat_map(|replaced| replaced.views.iter().copied())
+        .collect();
+    for name in db
+        .materialized_views
+        .keys()
+        .filter(|name| !code.materialized_views.contains_key(*name))
+        .filter(|name| !retired_views.contains(&name.as_str()))
+    {
+        plan.drift.push(format!("materialized view {name} exists but has no model"));
+    }
+    plan.drift.sort();
+
+    plan
+}
+
+/// How to drop a replaced table once its rows were copied.
+fn drop_replaced(keyspace: &str, db: &DbSchema, replaced: &ReplacedTable) -> String {
+    let drop_views = replaced
+        .views
+        .iter()
+        .filter(|view| db.materialized_views.contains_key(**view))
+        .map(|view| format!("DROP MATERIALIZED VIEW {keyspace}.{view}; "))
+        .collect::<String>();
+
+    format!(
+        "table {} was replaced by {}, drop it once you no longer need it: {drop_views}DROP TABLE {keyspace}.{};",
+        replaced.table, replaced.replaced_by, replaced.table
+    )
+}
+
+/// Copies every row of `from` into `to`, which has the same columns, through their JSON form.
+async fn copy_rows(session: &Session, keyspace: &str, from: &str, to: &str) -> anyhow::Result<u64> {
+    let insert = session
+        .prepare(format!("INSERT INTO {keyspace}.{to} JSON ?"))
+        .await?;
+    let mut rows = session
+        .query_iter(format!("SELECT JSON * FROM {keyspace}.{from}"), ())
+        .await?
+        .rows_stream::<(String,)>()?;
+
+    let mut copied = 0;
+    while let Some(row) = rows.next().await {
+        let (json,) = row?;
+        session.execute_unpaged(&insert, (json,)).await?;
+        copied += 1;
+    }
+
+    Ok(copied)
+}
+
+fn create_keyspace(keyspace: &str, settings: &MigrateSettings) -> String {
+    let replication = match settings.replication_strategy {
+        ReplicationStrategy::Simple => format!(
+            "'class': 'SimpleStrategy', 'replication_factor': {}",
+            settings.replication_factor
+        ),
+        ReplicationStrategy::NetworkTopology => {
+            let mut replication = String::from("'class': 'NetworkTopologyStrategy'");
+            if settings.datacenter_replication.is_empty() {
+                replication.push_str(&format!(", 'replication_factor': {}", settings.replication_factor));
+            }
+            for datacenter in &settings.datacenter_replication {
+                replication.push_str(&format!(", '{}': {}", datacenter.name, datacenter.replication_factor));
+            }
+            replication
+        }
+    };
+
+    format!("CREATE KEYSPACE IF NOT EXISTS {keyspace} WITH replication = {{{replication}}};")
+}
+
+fn keyspace_exists(session: &Session, keyspace: &str) -> bool {
+    session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .contains_key(keyspace)
+}
+
+/// Adds the fields missing from the database; type changes and removed fields are drift.
+fn diff_fields(
+    plan: &mut MigrationPlan,
+    kind: &str,
+    keyspace: &str,
+    name: &str,
+    existing: &SchemaObject,
+    model: &SchemaObject,
+) {
+    for (field, field_type, is_static) in &model.fields {
+        match existing.types_by_name.get(field) {
+            None => plan.statements.push(format!(
+                "ALTER {kind} {keyspace}.{name} ADD {field} {}{};",
+                normalize(field_type),
+                if *is_static { " static" } else { "" }
+            )),
+            Some(existing_type) if normalize(existing_type) != normalize(field_type) => {
+                plan.drift.push(format!(
+                    "{name}.{field} is {} in the database but {} in the model",
+                    normalize(existing_type),
+                    normalize(field_type)
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    for (field, _, _) in &existing.fields {
+        if !model.contains_field(field) && !RETIRED_FIELDS.contains(&(name, field.as_str())) {
+            plan.drift.push(format!("{name}.{field} exists but is not in the model"));
+        }
+    }
+}
+
+fn diff_keys(plan: &mut MigrationPlan, kind: &str, name: &str, existing: &SchemaObject, model: &SchemaObject) {
+    if existing.partition_keys != model.partition_keys || existing.clustering_keys != model.clustering_keys {
+        plan.drift.push(format!(
+            "{kind} {name} has primary key ({}) in the database but ({}) in the model",
+            primary_key(existing),
+            primary_key(model)
+        ));
+    }
+}
+
+fn fields_clause(object: &SchemaObject) -> String {
+    object
+        .fields
+        .iter()
+        .map(|(field, field_type, is_static)| {
+            format!(
+                "    {field} {}{}",
+                normalize(field_type),
+                if *is_static { " static" } else { "" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+fn primary_key(object: &SchemaObject) -> String {
+    let partition_keys = format!("({})", object.partition_keys.join(", "));
+
+    std::iter::once(partition_keys)
+        .chain(object.clustering_keys.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn options_clause(object: &SchemaObject) -> String {
+    match object.table_options.as_deref().map(str::trim) {
+        Some(options) if !options.is_empty() => format!("\nWITH {options}"),
+        _ => String::new(),
+    }
+}
+
+fn field_names(object: &SchemaObject) -> Vec<&str> {
+    let mut names: Vec<&str> = object.fields.iter().map(|(field, _, _)| field.as_str()).collect();
+    names.sort();
+    names
+}
+
+/// Models spell types the Rust way (`Frozen<Map<Text, Text>>`), the cluster the CQL way
+/// (`frozen<map<text, text>>`).
+fn normalize(field_type: &str) -> String {
+    field_type
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+        .replace(',', ", ")
+}
+
+fn sorted(objects: &SchemaObjects) -> BTreeMap<&String, &SchemaObject> {
+    objects.iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_schema(code: &CodeSchema) -> DbSchema {
+        DbSchema {
+            tables: code.tables.clone(),
+            udts: code.udts.clone(),
+            materialized_views: code.materialized_views.clone(),
+            keyspace_name: "bsky_rpg".to_string(),
+        }
+    }
+
+    fn set_field_type(object: &mut SchemaObject, field: &str, field_type: &str) {
+        for (name, existing_type, _) in object.fields.iter_mut() {
+            if name == field {
+                *existing_type = field_type.to_string();
+            }
+        }
+        object.types_by_name.insert(field.to_string(), field_type.to_string());
+    }
+
+    fn remove_field(object: &mut SchemaObject, field: &str) {
+        object.fields.retain(|(name, _, _)| name != field);
+        object.field_names.remove(field);
+        object.types_by_name.remove(field);
+    }
+
+    fn add_field(object: &mut SchemaObject, field: &str, field_type: &str) {
+        object.fields.push((field.to_string(), field_type.to_string(), false));
+        object.field_names.insert(field.to_string());
+        object.types_by_name.insert(field.to_string(), field_type.to_string());
+    }
+
+    #[test]
+    fn models_are_parsed() {
+        let code = code_schema();
+
+        assert_eq!(code.udts.len(), 1);
+        assert_eq!(code.tables.len(), 6);
+        assert_eq!(code.materialized_views.len(), 1);
+        assert_eq!(
+            code.tables["event_history"].clustering_keys,
+            vec!["event_at", "event_type", "event_id"]
+        );
+    }
+
+    #[test]
+    fn empty_keyspace_creates_everything_in_dependency_order() {
+        let code = code_schema();
+        let empty = DbSchema {
+            tables: SchemaObjects::new(),
+            udts: SchemaObjects::new(),
+            materialized_views: SchemaObjects::new(),
+            keyspace_name: "bsky_rpg".to_string(),
+        };
+
+        let plan = plan_migration("bsky_rpg", &empty, &code);
+
+        assert!(plan.drift.is_empty());
+        assert_eq!(plan.statements.len(), 8);
+        assert!(plan.statements[0].starts_with("CREATE TYPE IF NOT EXISTS bsky_rpg.leveling"));
+        assert!(plan.statements[1..7]
+            .iter()
+            .all(|statement| statement.starts_with("CREATE TABLE IF NOT EXISTS bsky_rpg.")));
+        assert!(plan.statements[7].starts_with("CREATE MATERIALIZED VIEW IF NOT EXISTS bsky_rpg.event_history_by_type"));
+    }
+
+    #[test]
+    fn up_to_date_schema_plans_nothing() {
+        let code = code_schema();
+
+        let plan = plan_migration("bsky_rpg", &live_schema(&code), &code);
+
+        assert!(plan.statements.is_empty());
+        assert!(plan.drift.is_empty());
+    }
+
+    #[test]
+    fn current_schema_json_matches_the_models() {
+        let snapshot: DbSchema = serde_json::from_str(include_str!("../current_schema.json")).unwrap();
+
+        let plan = plan_migration("bsky_rpg", &snapshot, &code_schema());
+
+        assert!(plan.statements.is_empty(), "{:?}", plan.statements);
+        assert!(plan.drift.is_empty(), "{:?}", plan.drift);
+    }
+
+    #[test]
+    fn missing_fields_are_added() {
+        let code = code_schema();
+        let mut db = live_schema(&code);
+        remove_field(db.udts.get_mut("leveling").unwrap(), "experience");
+        remove_field(db.tables.get_mut("characters").unwrap(), "description");
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+
+        assert_eq!(
+            plan.statements,
+            vec![
+                "ALTER TYPE bsky_rpg.leveling ADD experience bigint;",
+                "ALTER TABLE bsky_rpg.characters ADD description text;",
+            ]
+        );
+        assert!(plan.drift.is_empty());
+    }
+
+    #[test]
+    fn type_changes_and_unknown_objects_are_drift() {
+        let code = code_schema();
+        let mut db = live_schema(&code);
+        set_field_type(db.udts.get_mut("leveling").unwrap(), "experience", "int");
+        let characters = db.tables.get_mut("characters").unwrap();
+        characters.fields.push(("legacy".to_string(), "text".to_string(), false));
+        characters.field_names.insert("legacy".to_string());
+        characters.types_by_name.insert("legacy".to_string(), "text".to_string());
+        db.tables.insert("user_events".to_string(), SchemaObject::default());
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+
+        assert!(plan.statements.is_empty());
+        assert_eq!(
+            plan.drift,
+            vec![
+                "characters.legacy exists but is not in the model",
+                "leveling.experience is int in the database but bigint in the model",
+                "table user_events exists but has no model",
+            ]
+        );
+    }
+
+    #[test]
+    fn upgrade_to_64_bit_experience_ends_clean() {
+        let code = code_schema();
+        let mut db = live_schema(&code);
+        let leveling = db.udts.get_mut("leveling").unwrap();
+        set_field_type(leveling, "experience", "int");
+        set_field_type(leveling, "experience_to_next_level", "int");
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+        assert!(plan.statements.is_empty());
+        assert_eq!(plan.drift.len(), 2);
+
+        // ALTER TYPE bsky_rpg.leveling RENAME experience TO experience_int AND ...
+        let leveling = db.udts.get_mut("leveling").unwrap();
+        for field in ["experience", "experience_to_next_level"] {
+            remove_field(leveling, field);
+            add_field(leveling, &format!("{field}_int"), "int");
+        }
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+        assert_eq!(
+            plan.statements,
+            vec![
+                "ALTER TYPE bsky_rpg.leveling ADD experience bigint;",
+                "ALTER TYPE bsky_rpg.leveling ADD experience_to_next_level bigint;",
+            ]
+        );
+        assert!(plan.drift.is_empty(), "{:?}", plan.drift);
+
+        let leveling = db.udts.get_mut("leveling").unwrap();
+        add_field(leveling, "experience", "bigint");
+        add_field(leveling, "experience_to_next_level", "bigint");
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+        assert!(plan.statements.is_empty(), "{:?}", plan.statements);
+        assert!(plan.drift.is_empty(), "{:?}", plan.drift);
+    }
+
+    /// `live` as it was before `events` was replaced by `event_history`.
+    fn with_events_table(mut db: DbSchema) -> DbSchema {
+        let mut events = db.tables.remove("event_history").unwrap();
+        events.clustering_keys = vec!["event_at".to_string()];
+        events.table_options = None;
+        db.tables.insert("events".to_string(), events);
+
+        let mut events_by_type = db.materialized_views.remove("event_history_by_type").unwrap();
+        events_by_type.base_table = "events".to_string();
+        events_by_type.clustering_keys = vec!["event_at".to_string()];
+        db.materialized_views.insert("events_by_type".to_string(), events_by_type);
+
+        db
+    }
+
+    #[test]
+    fn replaced_events_table_is_copied_into_the_event_history() {
+        let code = code_schema();
+        let db = with_events_table(live_schema(&code));
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+
+        assert_eq!(plan.statements.len(), 2);
+        assert!(plan.statements[0].starts_with("CREATE TABLE IF NOT EXISTS bsky_rpg.event_history"));
+        assert!(plan.statements[0].contains("PRIMARY KEY ((user_did), event_at, event_type, event_id)"));
+        assert!(plan.statements[1].starts_with("CREATE MATERIALIZED VIEW IF NOT EXISTS bsky_rpg.event_history_by_type"));
+        assert_eq!(plan.copies, vec![("events".to_string(), "event_history".to_string())]);
+        assert!(plan.drift.is_empty(), "{:?}", plan.drift);
+        assert_eq!(
+            plan.retired,
+            vec![
+                "table events was replaced by event_history, drop it once you no longer need it: \
+                 DROP MATERIALIZED VIEW bsky_rpg.events_by_type; DROP TABLE bsky_rpg.events;"
+            ]
+        );
+    }
+
+    #[test]
+    fn replaced_table_left_in_place_is_not_drift() {
+        let code = code_schema();
+        let mut db = live_schema(&code);
+        let events = with_events_table(live_schema(&code));
+        db.tables.insert("events".to_string(), events.tables["events"].clone());
+
+        let plan = plan_migration("bsky_rpg", &db, &code);
+
+        assert!(plan.statements.is_empty());
+        assert!(plan.copies.is_empty());
+        assert!(plan.drift.is_empty());
+        assert_eq!(plan.retired.len(), 1);
+    }
+
+    #[test]
+    fn keyspace_replication() {
+        let settings = MigrateSettings {
+            dry_run: true,
+            replication_strategy: ReplicationStrategy::NetworkTopology,
+            replication_factor: 3,
+            datacenter_replication: vec![
+                crate::args::DatacenterReplication {
+                    name: "eu".to_string(),
+                    replication_factor: 3,
+                },
+                crate::args::DatacenterReplication {
+                    name: "us".to_string(),
+                    replication_factor: 2,
+                },
+            ],
+            schema_json: None,
+        };
+
+        assert_eq!(
+            create_keyspace("bsky_rpg", &settings),
+            "CREATE KEYSPACE IF NOT EXISTS bsky_rpg WITH replication = {'class': 'NetworkTopologyStrategy', 'eu': 3, 'us': 2};"
+        );
+    }
+}
@@ -1,11 +1,14 @@
-use crate::leveling::get_base_level_from_bsky_profile;
+use crate::events::AppBskyEventRecord;
+use crate::leveling::{calculate_experience, get_base_level_from_bsky_profile};
 use crate::models::udts::leveling::Leveling;
+use crate::rules::ExperienceRules;
 use atrium_api::app::bsky::actor::defs::ProfileViewDetailed;
+use atrium_api::app::bsky::actor::profile;
 use charybdis::macros::charybdis_model;
-use charybdis::types::Text;
+use charybdis::types::{Boolean, Text};
 use serde::Serialize;
 
-#[derive(Default, Serialize)]
+#[derive(Clone, Default, Serialize)]
 #[charybdis_model(
     table_name = characters,
     partition_keys = [user_did],
@@ -14,17 +17,53 @@ use serde::Serialize;
 pub struct Character {
     pub user_did: Text,           // profile_did
     pub name: Text,               // handle
+    pub display_name: Text,       // profile display name
+    pub description: Text,        // profile description
     pub leveling_state: Leveling, // udt leveling state
+    pub bootstrap_pending: Option<Boolean>, // created without its profile, see PendingBootstrap
 }
 
-impl From<ProfileViewDetailed> for Character {
-    fn from(response: ProfileViewDetailed) -> Self {
-        let level_response = get_base_level_from_bsky_profile(&response);
+partial_character!(CharacterProfile, user_did, display_name, description);
+
+impl Character {
+    /// Bootstraps a character from its Bsky profile, crediting past posts with their base experience.
+    pub fn from_profile(response: ProfileViewDetailed, rules: &ExperienceRules) -> Self {
+        let post_experience = rules.base_experience(&AppBskyEventRecord::Post.to_string());
+        let level_response = get_base_level_from_bsky_profile(&response, post_experience, rules.curve());
 
         Self {
             user_did: response.did.clone().to_string(),
             name: response.handle.clone().to_string(),
+            display_name: response.display_name.clone().unwrap_or_default(),
+            description: response.description.clone().unwrap_or_default(),
             leveling_state: Leveling::from(level_response),
+            bootstrap_pending: Some(false),
+        }
+    }
+
+    /// Characters stored before pending bootstraps existed read as `None`.
+    pub fn is_bootstrap_pending(&self) -> bool {
+        self.bootstrap_pending.unwrap_or_default()
+    }
+
+    /// A character whose profile couldn't be fetched: no past experience and no display data
+    /// until the bootstrap is reconciled.
+    pub fn pending(user_did: String, rules: &ExperienceRules) -> Self {
+        Self {
+            user_did,
+            leveling_state: Leveling::from(calculate_experience(rules.curve(), 0, 0)),
+            bootstrap_pending: Some(true),
+            ..Default::default()
+        }
+    }
+}
+
+impl CharacterProfile {
+    pub fn from_record(user_did: String, record: &profile::Record) -> Self {
+        Self {
+            user_did,
+            display_name: record.display_name.clone().unwrap_or_default(),
+            description: record.description.clone().unwrap_or_default(),
         }
     }
 }
@@ -12,13 +12,7 @@ pub struct CharacterExperience {
 }
 
 impl CharacterExperience {
-    pub fn get_experience(&self) -> i32 {
-        let exp = self.current_experience.0 as i32;
-
-        if exp < 0 {
-            0
-        } else {
-            exp
-        }
+    pub fn get_experience(&self) -> i64 {
+        self.current_experience.0.max(0)
     }
 }
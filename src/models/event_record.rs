@@ -0,0 +1,21 @@
+use charybdis::macros::charybdis_model;
+use charybdis::types::{Int, Map, Text, Timestamp};
+
+/// Lookup of a scored record by its collection and rkey, so deletes can be
+/// resolved back to the experience they originally granted.
+#[derive(Clone, Default)]
+#[charybdis_model(
+    table_name = event_records,
+    partition_keys = [user_did],
+    clustering_keys = [event_type, event_id]
+)]
+pub struct EventRecord {
+    pub user_did: Text,
+    pub event_type: Text,         // collection
+    pub event_id: Text,           // rkey
+    pub experience_gained: Int,
+    pub event_at: Timestamp,
+    /// Recipients credited for the engagement of this record, by their event type, so deleting
+    /// the record revokes their experience too.
+    pub received_by: Option<Map<Text, Text>>,
+}
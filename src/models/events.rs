@@ -2,12 +2,16 @@ use crate::models::udts::leveling::Leveling;
 use charybdis::macros::charybdis_model;
 use charybdis::types::{Frozen, Map, Text, Timestamp};
 
+/// The history of a user's scored events and revocations, newest first. Events sharing a
+/// timestamp (the credits of one post, likes in the same millisecond, backfilled records) are
+/// told apart by their type and id.
+#[derive(Clone)]
 #[charybdis_model(
-    table_name = events,
+    table_name = event_history,
     partition_keys = [user_did],
-    clustering_keys = [event_at],
+    clustering_keys = [event_at, event_type, event_id],
     table_options = r#"
-          CLUSTERING ORDER BY (event_at DESC)
+          CLUSTERING ORDER BY (event_at DESC, event_type ASC, event_id ASC)
     "#
 )]
 pub struct Events {
@@ -0,0 +1,14 @@
+use charybdis::macros::charybdis_model;
+use charybdis::types::{BigInt, Text, Timestamp};
+
+#[derive(Clone)]
+#[charybdis_model(
+    table_name = ingestion_cursors,
+    partition_keys = [instance_id],
+    clustering_keys = []
+)]
+pub struct IngestionCursor {
+    pub instance_id: Text,    // ingestion instance name
+    pub time_us: BigInt,      // last processed jetstream time_us
+    pub updated_at: Timestamp,
+}
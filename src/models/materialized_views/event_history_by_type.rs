@@ -3,11 +3,12 @@ use charybdis::macros::charybdis_view_model;
 use charybdis::types::{Frozen, Map, Text, Timestamp};
 
 #[charybdis_view_model(
-    base_table = events,
-    table_name = events_by_type,
+    base_table = event_history,
+    table_name = event_history_by_type,
     partition_keys = [user_did, event_type],
-    clustering_keys = [event_at],
+    clustering_keys = [event_at, event_id],
 )]
+#[allow(dead_code)] // only consumed by the schema migration
 pub struct Events {
     pub user_did: Text,
     pub event_type: Text,
@@ -1 +1 @@
-pub mod events_by_type;
+pub mod event_history_by_type;
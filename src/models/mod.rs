@@ -1,5 +1,8 @@
 pub mod character;
 pub mod character_experience;
+pub mod event_record;
 pub mod events;
+pub mod ingestion_cursor;
 pub mod materialized_views;
+pub mod pending_bootstrap;
 pub mod udts;
@@ -0,0 +1,17 @@
+use charybdis::macros::charybdis_model;
+use charybdis::types::{Int, Text, Timestamp};
+
+/// Characters created without their Bluesky profile, waiting for the reconciliation pass
+/// to credit their past posts and fill in their profile.
+#[derive(Clone, Default)]
+#[charybdis_model(
+    table_name = pending_bootstraps,
+    partition_keys = [user_did],
+    clustering_keys = []
+)]
+pub struct PendingBootstrap {
+    pub user_did: Text,
+    pub attempts: Int,          // failed profile lookups so far
+    pub last_error: Text,
+    pub pending_since: Timestamp,
+}
@@ -1,14 +1,16 @@
 use crate::leveling::LevelResponse;
 use charybdis::macros::charybdis_udt_model;
-use charybdis::types::{Float, Int};
+use charybdis::types::{BigInt, Float, Int};
 use serde::Serialize;
 
-#[derive(Default, Serialize)]
+#[derive(Clone, Default, Serialize)]
 #[charybdis_udt_model(type_name = leveling)]
 pub struct Leveling {
     pub level: Int,
-    pub experience: Int,
-    pub experience_to_next_level: Int,
+    #[scylla(default_when_null)] // null for rows written before the bigint migration
+    pub experience: BigInt,
+    #[scylla(default_when_null)]
+    pub experience_to_next_level: BigInt,
     pub levels_gained: Int,
     pub progress_percentage: Float,
 }
@@ -16,10 +18,10 @@ pub struct Leveling {
 impl From<LevelResponse> for Leveling {
     fn from(response: LevelResponse) -> Self {
         Self {
-            level: response.level.into(),
-            experience: response.experience.into(),
-            experience_to_next_level: response.experience_to_next_level.into(),
-            levels_gained: response._levels_gained.into(),
+            level: response.level,
+            experience: response.experience,
+            experience_to_next_level: response.experience_to_next_level,
+            levels_gained: response._levels_gained,
             progress_percentage: (response._progress_percentage * 100.0).round(),
         }
     }
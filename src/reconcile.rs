@@ -0,0 +1,264 @@
+use crate::events::user_locks::UserLocks;
+use crate::leveling::calculate_experience;
+use crate::models::character::Character;
+use crate::models::character_experience::CharacterExperience;
+use crate::models::pending_bootstrap::PendingBootstrap;
+use crate::repositories::DatabaseRepository;
+use crate::rules::{ExperienceRules, RulesStore};
+use charybdis::types::Counter;
+use futures::StreamExt;
+use paris::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Completes the pending bootstraps every `interval` until shutdown.
+///
+/// Runs next to the Jetstream consumer and shares its user locks, so a bootstrap is never
+/// completed while an event of the same user is being applied.
+pub async fn run_reconciler(
+    repository: Arc<DatabaseRepository>,
+    rules: Arc<RulesStore>,
+    locks: Arc<UserLocks>,
+    interval: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.wait_for(|shutdown| *shutdown) => return,
+        }
+
+        reconcile_pending_bootstraps(&repository, &rules.current(), &locks, &shutdown).await;
+    }
+}
+
+/// One pass over the pending bootstraps. Returns how many were completed.
+pub async fn reconcile_pending_bootstraps(
+    repository: &Arc<DatabaseRepository>,
+    rules: &ExperienceRules,
+    locks: &UserLocks,
+    shutdown: &watch::Receiver<bool>,
+) -> usize {
+    let mut pending_bootstraps = repository.character.find_pending_bootstraps().await;
+    let (mut completed, mut remaining) = (0, 0);
+
+    while let Some(pending) = pending_bootstraps.next().await {
+        if *shutdown.borrow() {
+            break;
+        }
+
+        let pending = match pending {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("Failed to read pending bootstrap: {}", e);
+                continue;
+            }
+        };
+
+        let _guard = locks.lock(&pending.user_did).await;
+        if complete_bootstrap(repository, rules, pending).await {
+            completed += 1;
+        } else {
+            remaining += 1;
+        }
+    }
+
+    if completed + remaining > 0 {
+        info!(
+            "Bootstrap reconciliation finished: {} completed, {} still pending",
+            completed, remaining
+        );
+    }
+
+    completed
+}
+
+/// Credits the past posts of a character created without its profile and fills in its
+/// profile data. Returns `false` if the bootstrap stays pending.
+async fn complete_bootstrap(
+    repository: &Arc<DatabaseRepository>,
+    rules: &ExperienceRules,
+    mut pending: PendingBootstrap,
+) -> bool {
+    let character = repository
+        .character
+        .find_by_partition_key(pending.user_did.clone())
+        .await;
+
+    let Some(mut character) = character.filter(Character::is_bootstrap_pending) else {
+        repository.character.delete_pending_bootstrap(&pending).await;
+        return true;
+    };
+
+    let response = match repository
+        .profiles
+        .get_author_profile(pending.user_did.clone())
+        .await
+    {
+        Ok(response) => response,
+        Err(e) if e.is_transient() => {
+            pending.attempts += 1;
+            pending.last_error = e.to_string();
+            repository.character.save_pending_bootstrap(&pending).await;
+            return false;
+        }
+        Err(e) => {
+            // the account is gone; the character keeps its pending flag and earned experience
+            warn!("Giving up the bootstrap of user {}: {}", pending.user_did, e);
+            repository.character.delete_pending_bootstrap(&pending).await;
+            return false;
+        }
+    };
+
+    let bootstrapped = Character::from_profile(response, rules);
+    let past_experience = bootstrapped.leveling_state.experience;
+
+    let current_experience = repository
+        .experience
+        .find_character_experience_by_partition_key(pending.user_did.clone())
+        .await
+        .map(|character_experience| character_experience.get_experience())
+        .unwrap_or_default();
+    let new_experience = current_experience.saturating_add(past_experience);
+    let leveling_response_dto = calculate_experience(rules.curve(), current_experience, new_experience);
+
+    // clear the flag before crediting, so a crash in between can't credit the posts twice
+    character.name = bootstrapped.name;
+    character.display_name = bootstrapped.display_name;
+    character.description = bootstrapped.description;
+    character.bootstrap_pending = Some(false);
+    repository
+        .character
+        .update_character(&mut character, leveling_response_dto)
+        .await;
+
+    repository
+        .experience
+        .increment_character_experience(
+            CharacterExperience {
+                user_did: pending.user_did.clone(),
+                current_experience: Counter(0),
+            },
+            past_experience,
+        )
+        .await;
+
+    repository.character.delete_pending_bootstrap(&pending).await;
+
+    info!(
+        "Completed the bootstrap of user {} with {} past experience",
+        pending.user_did, past_experience
+    );
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::profile_repository::{
+        FixtureProfileRepository, UnavailableProfileRepository,
+    };
+    use chrono::Utc;
+    use std::path::Path;
+
+    const ALICE: &str = "did:plc:alicefixture00000000000";
+
+    /// A character created while the AppView was down, with 30 experience earned since.
+    async fn pending_character(repository: &Arc<DatabaseRepository>, rules: &ExperienceRules) {
+        let mut character = Character::pending(ALICE.to_string(), rules);
+        repository
+            .character
+            .update_character(&mut character, calculate_experience(rules.curve(), 0, 30))
+            .await;
+        repository
+            .experience
+            .increment_character_experience(
+                CharacterExperience {
+                    user_did: ALICE.to_string(),
+                    current_experience: Counter(0),
+                },
+                30,
+            )
+            .await;
+        repository
+            .character
+            .save_pending_bootstrap(&PendingBootstrap {
+                user_did: ALICE.to_string(),
+                attempts: 1,
+                last_error: "rate limited by the AppView".to_string(),
+                pending_since: Utc::now(),
+            })
+            .await;
+    }
+
+    async fn pending_attempts(repository: &Arc<DatabaseRepository>) -> Vec<i32> {
+        repository
+            .character
+            .find_pending_bootstraps()
+            .await
+            .map(|pending| pending.unwrap().attempts)
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn pending_bootstrap_is_completed_once_the_profile_is_available() {
+        let mut repository = DatabaseRepository::in_memory();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles");
+        repository.profiles = Arc::new(FixtureProfileRepository::load(&fixtures).unwrap());
+        let repository = Arc::new(repository);
+        let rules = ExperienceRules::default();
+        let (_shutdown, shutdown_receiver) = watch::channel(false);
+        pending_character(&repository, &rules).await;
+
+        let completed =
+            reconcile_pending_bootstraps(&repository, &rules, &UserLocks::new(), &shutdown_receiver).await;
+
+        assert_eq!(completed, 1);
+        let character = repository
+            .character
+            .find_by_partition_key(ALICE.to_string())
+            .await
+            .unwrap();
+        assert!(!character.is_bootstrap_pending());
+        assert_eq!(character.name, "alice.test");
+        assert_eq!(character.leveling_state.experience, 30 + 42 * 30);
+
+        let experience = repository
+            .experience
+            .find_character_experience_by_partition_key(ALICE.to_string())
+            .await
+            .unwrap();
+        assert_eq!(experience.get_experience(), 30 + 42 * 30);
+        assert!(pending_attempts(&repository).await.is_empty());
+
+        // a second pass has nothing left to credit
+        let completed =
+            reconcile_pending_bootstraps(&repository, &rules, &UserLocks::new(), &shutdown_receiver).await;
+        assert_eq!(completed, 0);
+    }
+
+    #[tokio::test]
+    async fn bootstrap_stays_pending_while_the_appview_is_down() {
+        let mut repository = DatabaseRepository::in_memory();
+        repository.profiles = Arc::new(UnavailableProfileRepository);
+        let repository = Arc::new(repository);
+        let rules = ExperienceRules::default();
+        let (_shutdown, shutdown_receiver) = watch::channel(false);
+        pending_character(&repository, &rules).await;
+
+        let completed =
+            reconcile_pending_bootstraps(&repository, &rules, &UserLocks::new(), &shutdown_receiver).await;
+
+        assert_eq!(completed, 0);
+        assert_eq!(pending_attempts(&repository).await, vec![2]);
+        let character = repository
+            .character
+            .find_by_partition_key(ALICE.to_string())
+            .await
+            .unwrap();
+        assert!(character.is_bootstrap_pending());
+        assert_eq!(character.leveling_state.experience, 30);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::events::context::EventContext;
+use crate::events::AppBskyEventRecord;
+use crate::leveling::calculate_experience;
+use crate::repositories::DatabaseRepository;
+use crate::rules::ExperienceRules;
+use futures::StreamExt;
+use paris::{info, warn};
+use std::sync::Arc;
+
+/// Collections a misclassified event may actually belong to, in lookup order.
+const CANDIDATE_COLLECTIONS: [AppBskyEventRecord; 2] =
+    [AppBskyEventRecord::Like, AppBskyEventRecord::Repost];
+
+/// One-off repair for events stored before likes and reposts were classified correctly.
+///
+/// Every `app.bsky.feed.post` event without post context is resolved against the AppView
+/// and re-written with its real event type, subject context and event record.
+pub async fn repair_event_types(repository: &Arc<DatabaseRepository>) {
+    let post_type = AppBskyEventRecord::Post.to_string();
+    let mut events = repository.event.find_all_events().await;
+
+    let (mut repaired, mut unresolved) = (0, 0);
+
+    while let Some(event) = events.next().await {
+        let original = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Failed to read event: {}", e);
+                continue;
+            }
+        };
+
+        // real posts always carry their text
+        if original.event_type != post_type || original.event_data.contains_key("text") {
+            continue;
+        }
+        let mut event = original.clone();
+
+        let mut resolved = None;
+        for collection in CANDIDATE_COLLECTIONS {
+            let record = repository
+                .bsky
+                .get_record(
+                    event.user_did.clone(),
+                    collection.to_string(),
+                    event.event_id.clone(),
+                )
+                .await;
+
+            if let Some(record) = record {
+                resolved = Some((collection, record));
+                break;
+            }
+        }
+
+        let Some((collection, record)) = resolved else {
+            warn!(
+                "Could not resolve event {} of user {}",
+                event.event_id, event.user_did
+            );
+            unresolved += 1;
+            continue;
+        };
+
+        event.event_type = collection.to_string();
+        event.event_data = EventContext::from_record(&record).to_event_data();
+        repository.event.replace_event(&original, &event).await;
+
+        // move the lookup row to the right collection as well
+        let event_record = repository
+            .event
+            .find_event_record(
+                event.user_did.clone(),
+                post_type.clone(),
+                event.event_id.clone(),
+            )
+            .await;
+
+        if let Some(mut event_record) = event_record {
+            repository.event.delete_event_record(&event_record).await;
+            event_record.event_type = event.event_type.clone();
+            repository.event.update_event_record(&event_record).await;
+        }
+
+        info!(
+            "Re-classified event {} of user {} as {}",
+            event.event_id, event.user_did, event.event_type
+        );
+        repaired += 1;
+    }
+
+    info!(
+        "Event type repair finished: {} repaired, {} unresolved",
+        repaired, unresolved
+    );
+}
+
+/// Recomputes every character's leveling state from its experience counter, e.g. after
+/// changing the leveling curve or migrating the `leveling` UDT to 64-bit experience.
+pub async fn recompute_levels(repository: &Arc<DatabaseRepository>, rules: &ExperienceRules) {
+    let mut characters = repository.character.find_all_characters().await;
+    let mut recomputed = 0;
+
+    while let Some(character) = characters.next().await {
+        let mut character = match character {
+            Ok(character) => character,
+            Err(e) => {
+                warn!("Failed to read character: {}", e);
+                continue;
+            }
+        };
+
+        let experience = repository
+            .experience
+            .find_character_experience_by_partition_key(character.user_did.clone())
+            .await
+            .map(|character_experience| character_experience.get_experience())
+            .unwrap_or_default();
+
+        let leveling_response_dto = calculate_experience(rules.curve(), experience, experience);
+        repository
+            .character
+            .update_character(&mut character, leveling_response_dto)
+            .await;
+
+        recomputed += 1;
+    }
+
+    info!("Level recompute finished: {} characters updated", recomputed);
+}
@@ -1,37 +1,259 @@
+use crate::args::BskySettings;
+use crate::repositories::profile_repository::{ProfileError, ProfileRepository};
 use atrium_api::app::bsky::actor::defs::ProfileViewDetailed;
+use atrium_api::app::bsky::actor::{get_profile, get_profiles};
 use atrium_api::client::AtpServiceClient;
+use atrium_api::com::atproto::repo::list_records;
+use atrium_api::record::KnownRecord;
+use atrium_api::types::string::{AtIdentifier, Nsid};
+use atrium_api::types::TryFromUnknown;
+use atrium_api::xrpc::http::StatusCode;
 use atrium_xrpc_client::reqwest::ReqwestClient;
+use paris::warn;
+use rand::Rng;
+use std::fmt::{Debug, Display};
+use std::future::Future;
 use std::str::FromStr;
+use std::time::Duration;
 
 pub type BskyClient = AtpServiceClient<ReqwestClient>;
 
 pub struct BskyRepository {
     client: BskyClient,
+    retry: RetryPolicy,
 }
 
 impl BskyRepository {
-    pub fn new(uri: String) -> Self {
+    pub fn new(uri: String, retry: RetryPolicy) -> Self {
         let client = AtpServiceClient::new(ReqwestClient::new(uri));
-        Self { client }
+        Self { client, retry }
     }
 
-    pub async fn get_author_profile(&self, author: String) -> ProfileViewDetailed {
+    /// Fetches a single record, returning `None` if it doesn't exist (anymore) or can't be parsed.
+    pub async fn get_record(
+        &self,
+        repo: String,
+        collection: String,
+        rkey: String,
+    ) -> Option<KnownRecord> {
         let response = self
             .client
             .service
-            .app
-            .bsky
-            .actor
-            .get_profile(
-                atrium_api::app::bsky::actor::get_profile::ParametersData {
-                    actor: atrium_api::types::string::AtIdentifier::from_str(&author)
-                        .expect("Failed to create AtIdentifier"),
+            .com
+            .atproto
+            .repo
+            .get_record(
+                atrium_api::com::atproto::repo::get_record::ParametersData {
+                    cid: None,
+                    collection: Nsid::new(collection).ok()?,
+                    repo: AtIdentifier::from_str(&repo).ok()?,
+                    rkey,
                 }
                 .into(),
             )
             .await
-            .expect("Failed to get profile");
+            .ok()?;
 
-        response
+        KnownRecord::try_from_unknown(response.data.value).ok()
+    }
+
+    /// One page of `com.atproto.repo.listRecords`, oldest records first. Served by the PDS
+    /// hosting the repository rather than the AppView.
+    pub async fn list_records(
+        &self,
+        repo: &str,
+        collection: &str,
+        cursor: Option<String>,
+    ) -> anyhow::Result<list_records::Output> {
+        let response = self
+            .client
+            .service
+            .com
+            .atproto
+            .repo
+            .list_records(
+                list_records::ParametersData {
+                    collection: Nsid::new(collection.to_string()).map_err(anyhow::Error::msg)?,
+                    cursor,
+                    limit: Some(100.try_into().map_err(anyhow::Error::msg)?),
+                    repo: AtIdentifier::from_str(repo).map_err(anyhow::Error::msg)?,
+                    reverse: Some(true),
+                    rkey_end: None,
+                    rkey_start: None,
+                }
+                .into(),
+            )
+            .await?;
+
+        Ok(response)
+    }
+}
+
+#[async_trait::async_trait]
+impl ProfileRepository for BskyRepository {
+    async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError> {
+        let actor = AtIdentifier::from_str(&author).map_err(|e| ProfileError::Invalid(e.to_string()))?;
+
+        self.retry
+            .run(&format!("Profile lookup of {}", author), || async {
+                self.client
+                    .service
+                    .app
+                    .bsky
+                    .actor
+                    .get_profile(get_profile::ParametersData { actor: actor.clone() }.into())
+                    .await
+                    .map_err(|e| profile_error(&author, e))
+            })
+            .await
+    }
+
+    /// One `app.bsky.actor.getProfiles` request. Accounts without a profile are left out of
+    /// the response rather than failing it.
+    async fn get_profiles(&self, authors: Vec<String>) -> Result<Vec<ProfileViewDetailed>, ProfileError> {
+        let actors = authors
+            .iter()
+            .map(|author| AtIdentifier::from_str(author).map_err(|e| ProfileError::Invalid(e.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let description = authors.join(", ");
+
+        let response = self
+            .retry
+            .run(&format!("Profiles lookup of {}", description), || async {
+                self.client
+                    .service
+                    .app
+                    .bsky
+                    .actor
+                    .get_profiles(get_profiles::ParametersData { actors: actors.clone() }.into())
+                    .await
+                    .map_err(|e| profile_error(&description, e))
+            })
+            .await?;
+
+        Ok(response.data.profiles)
+    }
+}
+
+/// Sorts an XRPC failure into permanent and transient errors.
+fn profile_error<E: Debug + Display>(author: &str, error: atrium_api::xrpc::Error<E>) -> ProfileError {
+    match error {
+        atrium_api::xrpc::Error::XrpcResponse(response) => {
+            let status = response.status;
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                ProfileError::RateLimited
+            } else if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+                ProfileError::Unavailable(response.to_string())
+            } else {
+                // e.g. 400 "Profile not found", "Account is deactivated" or "Account has been suspended"
+                ProfileError::NotFound {
+                    author: author.to_string(),
+                    reason: response.to_string(),
+                }
+            }
+        }
+        atrium_api::xrpc::Error::HttpClient(e) => ProfileError::Unavailable(e.to_string()),
+        e => ProfileError::Invalid(e.to_string()),
+    }
+}
+
+/// Retries of transient AppView failures: exponential backoff with full jitter, so workers
+/// that failed together don't retry together.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl From<&BskySettings> for RetryPolicy {
+    fn from(settings: &BskySettings) -> Self {
+        Self {
+            max_retries: settings.bsky_max_retries,
+            base_delay: Duration::from_millis(settings.bsky_retry_base_delay_ms),
+            max_delay: Duration::from_millis(settings.bsky_retry_max_delay_ms),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A random delay up to `base * 2^(attempt - 1)`, capped at the maximum. Rate limited
+    /// requests wait at least half of the cap.
+    pub fn delay(&self, attempt: u32, rate_limited: bool) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let cap = self.base_delay.saturating_mul(1 << exponent).min(self.max_delay);
+        let floor = if rate_limited { cap / 2 } else { Duration::ZERO };
+
+        rand::thread_rng().gen_range(floor..=cap)
+    }
+
+    /// Runs `request` until it succeeds, fails permanently or runs out of retries.
+    pub async fn run<T, F, Fut>(&self, description: &str, mut request: F) -> Result<T, ProfileError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ProfileError>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let error = match request().await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+
+            attempt += 1;
+            if !error.is_transient() || attempt > self.max_retries {
+                return Err(error);
+            }
+
+            let delay = self.delay(attempt, matches!(error, ProfileError::RateLimited));
+            warn!(
+                "{} failed ({}), retrying in {:?} (attempt {})",
+                description, error, delay, attempt
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delays_are_jittered_within_the_backoff() {
+        let retry = RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1_000),
+        };
+
+        for _ in 0..100 {
+            assert!(retry.delay(1, false) <= Duration::from_millis(100));
+            assert!(retry.delay(3, false) <= Duration::from_millis(400));
+            assert!(retry.delay(10, false) <= Duration::from_millis(1_000));
+
+            let rate_limited = retry.delay(3, true);
+            assert!(rate_limited >= Duration::from_millis(200));
+            assert!(rate_limited <= Duration::from_millis(400));
+        }
+    }
+
+    #[test]
+    fn only_throttling_and_server_errors_are_retried() {
+        let response = |status: StatusCode| {
+            atrium_api::xrpc::Error::<get_profile::Error>::XrpcResponse(atrium_api::xrpc::error::XrpcError {
+                status,
+                error: None,
+            })
+        };
+
+        assert!(matches!(
+            profile_error("alice.test", response(StatusCode::TOO_MANY_REQUESTS)),
+            ProfileError::RateLimited
+        ));
+        assert!(profile_error("alice.test", response(StatusCode::BAD_GATEWAY)).is_transient());
+        assert!(!profile_error("alice.test", response(StatusCode::BAD_REQUEST)).is_transient());
+        assert!(!profile_error("alice.test", atrium_api::xrpc::Error::<get_profile::Error>::UnexpectedResponseType).is_transient());
     }
 }
@@ -1,24 +1,49 @@
 use crate::leveling::LevelResponse;
-use crate::models::character::Character;
-
-use crate::models::character_experience::CharacterExperience;
+use crate::models::character::{Character, CharacterProfile};
+use crate::models::pending_bootstrap::PendingBootstrap;
 use crate::models::udts::leveling::Leveling;
-use charybdis::operations::{Find, Insert};
-use charybdis::types::Counter;
+use crate::repositories::OperationConsistency;
+use charybdis::operations::{Delete, Find, Insert, Update};
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use scylla::CachingSession;
 use std::sync::Arc;
 
-pub struct CharacterRepository {
+#[async_trait::async_trait]
+pub trait CharacterRepository {
+    async fn find_by_partition_key(&self, user_did: String) -> Option<Character>;
+
+    async fn find_all_characters(&self) -> BoxStream<'static, anyhow::Result<Character>>;
+
+    /// Stores `response` as the character's leveling state and persists the whole character.
+    async fn update_character(&self, character: &mut Character, response: LevelResponse);
+
+    async fn update_character_profile(&self, profile: &CharacterProfile);
+
+    async fn save_pending_bootstrap(&self, pending: &PendingBootstrap);
+
+    async fn find_pending_bootstraps(&self) -> BoxStream<'static, anyhow::Result<PendingBootstrap>>;
+
+    async fn delete_pending_bootstrap(&self, pending: &PendingBootstrap);
+}
+
+pub struct ScyllaCharacterRepository {
     pub session: Arc<CachingSession>,
+    consistency: OperationConsistency,
 }
 
-impl CharacterRepository {
-    pub fn new(connection: Arc<CachingSession>) -> Self {
+impl ScyllaCharacterRepository {
+    pub fn new(connection: Arc<CachingSession>, consistency: OperationConsistency) -> Self {
         Self {
             session: connection,
+            consistency,
         }
     }
-    pub async fn find_by_partition_key(&self, user_did: String) -> Option<Character> {
+}
+
+#[async_trait::async_trait]
+impl CharacterRepository for ScyllaCharacterRepository {
+    async fn find_by_partition_key(&self, user_did: String) -> Option<Character> {
         let character = Character {
             user_did,
             ..Default::default()
@@ -26,45 +51,66 @@ impl CharacterRepository {
 
         character
             .maybe_find_by_primary_key()
+            .consistency(self.consistency.read)
             .execute(&self.session)
             .await
             .unwrap()
     }
 
-    pub async fn find_character_experience_by_partition_key(
-        &self,
-        user_did: String,
-    ) -> Option<CharacterExperience> {
-        let character_experience = CharacterExperience {
-            user_did,
-            current_experience: Counter(0),
-        };
+    async fn find_all_characters(&self) -> BoxStream<'static, anyhow::Result<Character>> {
+        Character::find_all()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .expect("Failed to scan characters")
+            .map(|character| character.map_err(anyhow::Error::from))
+            .boxed()
+    }
 
-        character_experience
-            .maybe_find_by_primary_key()
+    async fn update_character(&self, character: &mut Character, response: LevelResponse) {
+        character.leveling_state = Leveling::from(response);
+        character
+            .insert()
+            .consistency(self.consistency.write)
             .execute(&self.session)
             .await
-            .unwrap()
+            .expect("Failed to update character");
     }
 
-    pub async fn increment_character_experience(
-        &self,
-        character_experience: CharacterExperience,
-        experience_points: i64,
-    ) {
-        character_experience
-            .increment_current_experience(experience_points)
+    async fn update_character_profile(&self, profile: &CharacterProfile) {
+        profile
+            .update()
+            .consistency(self.consistency.write)
             .execute(&self.session)
             .await
-            .expect("Failed to increment experience");
+            .expect("Failed to update character profile");
     }
 
-    pub async fn update_character(&self, character: &mut Character, response: LevelResponse) {
-        character.leveling_state = Leveling::from(response);
-        character
+    async fn save_pending_bootstrap(&self, pending: &PendingBootstrap) {
+        pending
             .insert()
+            .consistency(self.consistency.write)
             .execute(&self.session)
             .await
-            .expect("Failed to update character");
+            .expect("Failed to save pending bootstrap");
+    }
+
+    async fn find_pending_bootstraps(&self) -> BoxStream<'static, anyhow::Result<PendingBootstrap>> {
+        PendingBootstrap::find_all()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .expect("Failed to scan pending bootstraps")
+            .map(|pending| pending.map_err(anyhow::Error::from))
+            .boxed()
+    }
+
+    async fn delete_pending_bootstrap(&self, pending: &PendingBootstrap) {
+        pending
+            .delete()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to delete pending bootstrap");
     }
 }
@@ -0,0 +1,74 @@
+use crate::models::ingestion_cursor::IngestionCursor;
+use crate::repositories::OperationConsistency;
+use charybdis::operations::{Find, Insert};
+use chrono::Utc;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use scylla::CachingSession;
+use std::sync::Arc;
+
+#[async_trait::async_trait]
+pub trait CursorRepository {
+    async fn find_cursor(&self, instance_id: String) -> Option<IngestionCursor>;
+
+    async fn save_cursor(&self, instance_id: String, time_us: u64);
+
+    async fn find_all_cursors(&self) -> BoxStream<'static, anyhow::Result<IngestionCursor>>;
+}
+
+pub struct ScyllaCursorRepository {
+    pub session: Arc<CachingSession>,
+    consistency: OperationConsistency,
+}
+
+impl ScyllaCursorRepository {
+    pub fn new(connection: Arc<CachingSession>, consistency: OperationConsistency) -> Self {
+        Self {
+            session: connection,
+            consistency,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CursorRepository for ScyllaCursorRepository {
+    async fn find_cursor(&self, instance_id: String) -> Option<IngestionCursor> {
+        let cursor = IngestionCursor {
+            instance_id,
+            time_us: 0,
+            updated_at: Utc::now(),
+        };
+
+        cursor
+            .maybe_find_by_primary_key()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .unwrap()
+    }
+
+    async fn save_cursor(&self, instance_id: String, time_us: u64) {
+        let cursor = IngestionCursor {
+            instance_id,
+            time_us: time_us as i64,
+            updated_at: Utc::now(),
+        };
+
+        cursor
+            .insert()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to save cursor");
+    }
+
+    async fn find_all_cursors(&self) -> BoxStream<'static, anyhow::Result<IngestionCursor>> {
+        IngestionCursor::find_all()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .expect("Failed to scan cursors")
+            .map(|cursor| cursor.map_err(anyhow::Error::from))
+            .boxed()
+    }
+}
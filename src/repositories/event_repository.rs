@@ -1,37 +1,184 @@
 use crate::events::dto::NewEventDTO;
+use crate::events::received::received_engagements;
 use crate::leveling::LevelResponse;
+use crate::models::event_record::EventRecord;
 use crate::models::events::Events;
 use crate::models::udts::leveling::Leveling;
-use charybdis::operations::Insert;
+use crate::repositories::OperationConsistency;
+use charybdis::operations::{Delete, Find, Insert};
 use charybdis::types::Timestamp;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use scylla::CachingSession;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-pub struct EventRepository {
+#[async_trait::async_trait]
+pub trait EventRepository {
+    async fn insert_event(&self, payload: &NewEventDTO, level_response: LevelResponse);
+
+    async fn find_all_events(&self) -> BoxStream<'static, anyhow::Result<Events>>;
+
+    /// Replaces `old` by `event`, which may have another type.
+    async fn replace_event(&self, old: &Events, event: &Events);
+
+    async fn insert_event_record(&self, payload: &NewEventDTO, experience_gained: i32);
+
+    async fn update_event_record(&self, record: &EventRecord);
+
+    async fn find_event_record(
+        &self,
+        user_did: String,
+        event_type: String,
+        event_id: String,
+    ) -> Option<EventRecord>;
+
+    async fn find_event_records(&self, user_did: String) -> BoxStream<'static, anyhow::Result<EventRecord>>;
+
+    async fn delete_event_record(&self, record: &EventRecord);
+}
+
+/// When the event happened; `posted_at` is in microseconds, like Jetstream's `time_us`.
+fn event_at(payload: &NewEventDTO) -> Timestamp {
+    Timestamp::from_timestamp_micros(payload.posted_at as i64).unwrap_or_default()
+}
+
+/// The history row of a scored event.
+pub(crate) fn new_event(payload: &NewEventDTO, level_response: LevelResponse) -> Events {
+    Events {
+        user_did: payload.user_did.to_string(),
+        event_type: payload.event_type.to_string(),
+        event_id: payload.event_id.to_string(),
+        event_data: payload.context.to_event_data(),
+        leveling_state: Leveling::from(level_response),
+        event_at: event_at(payload),
+    }
+}
+
+/// The lookup row of a scored event.
+pub(crate) fn new_event_record(payload: &NewEventDTO, experience_gained: i32) -> EventRecord {
+    EventRecord {
+        user_did: payload.user_did.to_string(),
+        event_type: payload.event_type.to_string(),
+        event_id: payload.event_id.to_string(),
+        experience_gained,
+        event_at: event_at(payload),
+        received_by: Some(
+            received_engagements(payload)
+                .into_iter()
+                .map(|engagement| (engagement.event_type, engagement.recipient_did))
+                .collect::<HashMap<_, _>>(),
+        )
+        .filter(|received_by| !received_by.is_empty()),
+    }
+}
+
+pub struct ScyllaEventRepository {
     pub session: Arc<CachingSession>,
+    consistency: OperationConsistency,
 }
 
-impl EventRepository {
-    pub fn new(connection: Arc<CachingSession>) -> Self {
+impl ScyllaEventRepository {
+    pub fn new(connection: Arc<CachingSession>, consistency: OperationConsistency) -> Self {
         Self {
             session: Arc::clone(&connection),
+            consistency,
         }
     }
+}
 
-    pub async fn insert_event(&self, payload: &NewEventDTO, level_response: LevelResponse) {
-        let event = Events {
-            user_did: payload.user_did.to_string(),
-            event_type: payload.event_type.to_string(),
-            event_id: payload.event_id.to_string(),
-            event_data: payload.context.clone(),
-            leveling_state: Leveling::from(level_response),
-            event_at: Timestamp::from_timestamp_nanos(payload.posted_at as i64),
-        };
+#[async_trait::async_trait]
+impl EventRepository for ScyllaEventRepository {
+    async fn insert_event(&self, payload: &NewEventDTO, level_response: LevelResponse) {
+        new_event(payload, level_response)
+            .insert()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    async fn find_all_events(&self) -> BoxStream<'static, anyhow::Result<Events>> {
+        Events::find_all()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .expect("Failed to scan events")
+            .map(|event| event.map_err(anyhow::Error::from))
+            .boxed()
+    }
 
+    async fn replace_event(&self, old: &Events, event: &Events) {
+        // the type is part of the primary key; insert first so a failure can't lose the event
         event
             .insert()
+            .consistency(self.consistency.write)
             .execute(&self.session)
             .await
             .expect("Failed to insert event");
+
+        old.delete()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to delete event");
+    }
+
+    async fn insert_event_record(&self, payload: &NewEventDTO, experience_gained: i32) {
+        new_event_record(payload, experience_gained)
+            .insert()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to insert event record");
+    }
+
+    async fn update_event_record(&self, record: &EventRecord) {
+        record
+            .insert()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to update event record");
+    }
+
+    async fn find_event_record(
+        &self,
+        user_did: String,
+        event_type: String,
+        event_id: String,
+    ) -> Option<EventRecord> {
+        let record = EventRecord {
+            user_did,
+            event_type,
+            event_id,
+            ..Default::default()
+        };
+
+        record
+            .maybe_find_by_primary_key()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .unwrap()
+    }
+
+    async fn find_event_records(&self, user_did: String) -> BoxStream<'static, anyhow::Result<EventRecord>> {
+        EventRecord::find_by_partition_key_value((user_did,))
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .expect("Failed to find event records")
+            .map(|record| record.map_err(anyhow::Error::from))
+            .boxed()
+    }
+
+    async fn delete_event_record(&self, record: &EventRecord) {
+        record
+            .delete()
+            .consistency(self.consistency.write)
+            .execute(&self.session)
+            .await
+            .expect("Failed to delete event record");
     }
 }
@@ -0,0 +1,88 @@
+use crate::models::character_experience::CharacterExperience;
+use crate::repositories::OperationConsistency;
+use charybdis::operations::Find;
+use charybdis::types::Counter;
+use scylla::CachingSession;
+use std::sync::Arc;
+
+/// The experience counters, kept apart from the characters because counters can only live
+/// in counter tables.
+#[async_trait::async_trait]
+pub trait ExperienceRepository {
+    async fn find_character_experience_by_partition_key(
+        &self,
+        user_did: String,
+    ) -> Option<CharacterExperience>;
+
+    async fn increment_character_experience(
+        &self,
+        character_experience: CharacterExperience,
+        experience_points: i64,
+    );
+
+    async fn decrement_character_experience(
+        &self,
+        character_experience: CharacterExperience,
+        experience_points: i64,
+    );
+}
+
+pub struct ScyllaExperienceRepository {
+    pub session: Arc<CachingSession>,
+    consistency: OperationConsistency,
+}
+
+impl ScyllaExperienceRepository {
+    pub fn new(connection: Arc<CachingSession>, consistency: OperationConsistency) -> Self {
+        Self {
+            session: connection,
+            consistency,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExperienceRepository for ScyllaExperienceRepository {
+    async fn find_character_experience_by_partition_key(
+        &self,
+        user_did: String,
+    ) -> Option<CharacterExperience> {
+        let character_experience = CharacterExperience {
+            user_did,
+            current_experience: Counter(0),
+        };
+
+        character_experience
+            .maybe_find_by_primary_key()
+            .consistency(self.consistency.read)
+            .execute(&self.session)
+            .await
+            .unwrap()
+    }
+
+    async fn increment_character_experience(
+        &self,
+        character_experience: CharacterExperience,
+        experience_points: i64,
+    ) {
+        character_experience
+            .increment_current_experience(experience_points)
+            .consistency(self.consistency.counter)
+            .execute(&self.session)
+            .await
+            .expect("Failed to increment experience");
+    }
+
+    async fn decrement_character_experience(
+        &self,
+        character_experience: CharacterExperience,
+        experience_points: i64,
+    ) {
+        character_experience
+            .decrement_current_experience(experience_points)
+            .consistency(self.consistency.counter)
+            .execute(&self.session)
+            .await
+            .expect("Failed to decrement experience");
+    }
+}
@@ -0,0 +1,291 @@
+use crate::events::dto::NewEventDTO;
+use crate::leveling::LevelResponse;
+use crate::models::character::{Character, CharacterProfile};
+use crate::models::character_experience::CharacterExperience;
+use crate::models::event_record::EventRecord;
+use crate::models::events::Events;
+use crate::models::ingestion_cursor::IngestionCursor;
+use crate::models::pending_bootstrap::PendingBootstrap;
+use crate::models::udts::leveling::Leveling;
+use crate::repositories::character_repository::CharacterRepository;
+use crate::repositories::cursor_repository::CursorRepository;
+use crate::repositories::event_repository::{new_event, new_event_record, EventRepository};
+use crate::repositories::experience_repository::ExperienceRepository;
+use charybdis::types::{Counter, Timestamp};
+use chrono::Utc;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// `(user_did, event_at, event_type, event_id)`, the primary key of `event_history`.
+type EventKey = (String, Timestamp, String, String);
+
+fn event_key(event: &Events) -> EventKey {
+    (
+        event.user_did.clone(),
+        event.event_at,
+        event.event_type.clone(),
+        event.event_id.clone(),
+    )
+}
+
+#[derive(Default)]
+struct Tables {
+    characters: HashMap<String, Character>,
+    experience: HashMap<String, i64>,
+    events: BTreeMap<EventKey, Events>,
+    event_records: BTreeMap<(String, String, String), EventRecord>,
+    cursors: HashMap<String, IngestionCursor>,
+    pending_bootstraps: BTreeMap<String, PendingBootstrap>,
+}
+
+/// Every table in memory, keyed by the same primary keys as the Scylla schema so writes
+/// upsert the way they do in the database.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl CharacterRepository for InMemoryRepository {
+    async fn find_by_partition_key(&self, user_did: String) -> Option<Character> {
+        self.tables.lock().unwrap().characters.get(&user_did).cloned()
+    }
+
+    async fn find_all_characters(&self) -> BoxStream<'static, anyhow::Result<Character>> {
+        let characters: Vec<_> = self
+            .tables
+            .lock()
+            .unwrap()
+            .characters
+            .values()
+            .cloned()
+            .map(Ok)
+            .collect();
+
+        futures::stream::iter(characters).boxed()
+    }
+
+    async fn update_character(&self, character: &mut Character, response: LevelResponse) {
+        character.leveling_state = Leveling::from(response);
+        self.tables
+            .lock()
+            .unwrap()
+            .characters
+            .insert(character.user_did.clone(), character.clone());
+    }
+
+    async fn update_character_profile(&self, profile: &CharacterProfile) {
+        let mut tables = self.tables.lock().unwrap();
+        let character = tables
+            .characters
+            .entry(profile.user_did.clone())
+            .or_insert_with(|| Character {
+                user_did: profile.user_did.clone(),
+                ..Default::default()
+            });
+
+        character.display_name = profile.display_name.clone();
+        character.description = profile.description.clone();
+    }
+
+    async fn save_pending_bootstrap(&self, pending: &PendingBootstrap) {
+        self.tables
+            .lock()
+            .unwrap()
+            .pending_bootstraps
+            .insert(pending.user_did.clone(), pending.clone());
+    }
+
+    async fn find_pending_bootstraps(&self) -> BoxStream<'static, anyhow::Result<PendingBootstrap>> {
+        let pending: Vec<_> = self
+            .tables
+            .lock()
+            .unwrap()
+            .pending_bootstraps
+            .values()
+            .cloned()
+            .map(Ok)
+            .collect();
+
+        futures::stream::iter(pending).boxed()
+    }
+
+    async fn delete_pending_bootstrap(&self, pending: &PendingBootstrap) {
+        self.tables
+            .lock()
+            .unwrap()
+            .pending_bootstraps
+            .remove(&pending.user_did);
+    }
+}
+
+#[async_trait::async_trait]
+impl ExperienceRepository for InMemoryRepository {
+    async fn find_character_experience_by_partition_key(
+        &self,
+        user_did: String,
+    ) -> Option<CharacterExperience> {
+        let tables = self.tables.lock().unwrap();
+        let experience = *tables.experience.get(&user_did)?;
+
+        Some(CharacterExperience {
+            user_did,
+            current_experience: Counter(experience),
+        })
+    }
+
+    async fn increment_character_experience(
+        &self,
+        character_experience: CharacterExperience,
+        experience_points: i64,
+    ) {
+        let mut tables = self.tables.lock().unwrap();
+        let experience = tables
+            .experience
+            .entry(character_experience.user_did)
+            .or_default();
+
+        *experience = experience.wrapping_add(experience_points);
+    }
+
+    async fn decrement_character_experience(
+        &self,
+        character_experience: CharacterExperience,
+        experience_points: i64,
+    ) {
+        let mut tables = self.tables.lock().unwrap();
+        let experience = tables
+            .experience
+            .entry(character_experience.user_did)
+            .or_default();
+
+        *experience = experience.wrapping_sub(experience_points);
+    }
+}
+
+#[async_trait::async_trait]
+impl EventRepository for InMemoryRepository {
+    async fn insert_event(&self, payload: &NewEventDTO, level_response: LevelResponse) {
+        let event = new_event(payload, level_response);
+        self.tables.lock().unwrap().events.insert(event_key(&event), event);
+    }
+
+    async fn find_all_events(&self) -> BoxStream<'static, anyhow::Result<Events>> {
+        let events: Vec<_> = self
+            .tables
+            .lock()
+            .unwrap()
+            .events
+            .values()
+            .cloned()
+            .map(Ok)
+            .collect();
+
+        futures::stream::iter(events).boxed()
+    }
+
+    async fn replace_event(&self, old: &Events, event: &Events) {
+        let mut tables = self.tables.lock().unwrap();
+        tables.events.remove(&event_key(old));
+        tables.events.insert(event_key(event), event.clone());
+    }
+
+    async fn insert_event_record(&self, payload: &NewEventDTO, experience_gained: i32) {
+        self.update_event_record(&new_event_record(payload, experience_gained))
+            .await;
+    }
+
+    async fn update_event_record(&self, record: &EventRecord) {
+        let key = (
+            record.user_did.clone(),
+            record.event_type.clone(),
+            record.event_id.clone(),
+        );
+        self.tables
+            .lock()
+            .unwrap()
+            .event_records
+            .insert(key, record.clone());
+    }
+
+    async fn find_event_record(
+        &self,
+        user_did: String,
+        event_type: String,
+        event_id: String,
+    ) -> Option<EventRecord> {
+        self.tables
+            .lock()
+            .unwrap()
+            .event_records
+            .get(&(user_did, event_type, event_id))
+            .cloned()
+    }
+
+    async fn find_event_records(&self, user_did: String) -> BoxStream<'static, anyhow::Result<EventRecord>> {
+        let records: Vec<_> = self
+            .tables
+            .lock()
+            .unwrap()
+            .event_records
+            .values()
+            .filter(|record| record.user_did == user_did)
+            .cloned()
+            .map(Ok)
+            .collect();
+
+        futures::stream::iter(records).boxed()
+    }
+
+    async fn delete_event_record(&self, record: &EventRecord) {
+        let key = (
+            record.user_did.clone(),
+            record.event_type.clone(),
+            record.event_id.clone(),
+        );
+        self.tables.lock().unwrap().event_records.remove(&key);
+    }
+}
+
+#[async_trait::async_trait]
+impl CursorRepository for InMemoryRepository {
+    async fn find_cursor(&self, instance_id: String) -> Option<IngestionCursor> {
+        self.tables.lock().unwrap().cursors.get(&instance_id).cloned()
+    }
+
+    async fn save_cursor(&self, instance_id: String, time_us: u64) {
+        let cursor = IngestionCursor {
+            instance_id: instance_id.clone(),
+            time_us: time_us as i64,
+            updated_at: Utc::now(),
+        };
+
+        self.tables
+            .lock()
+            .unwrap()
+            .cursors
+            .insert(instance_id, cursor);
+    }
+
+    async fn find_all_cursors(&self) -> BoxStream<'static, anyhow::Result<IngestionCursor>> {
+        let cursors: Vec<_> = self
+            .tables
+            .lock()
+            .unwrap()
+            .cursors
+            .values()
+            .cloned()
+            .map(Ok)
+            .collect();
+
+        futures::stream::iter(cursors).boxed()
+    }
+}
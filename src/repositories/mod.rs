@@ -1,25 +1,118 @@
-mod bsky_repository;
+pub mod bsky_repository;
 pub mod character_repository;
+pub mod cursor_repository;
 pub mod event_repository;
+pub mod experience_repository;
+pub mod profile_batcher;
+pub mod profile_cache;
+pub mod profile_repository;
+#[cfg(test)]
+pub mod memory;
 
-use crate::repositories::bsky_repository::BskyRepository;
-use crate::repositories::character_repository::CharacterRepository;
-use crate::repositories::event_repository::EventRepository;
+use crate::args::{BskySettings, DatabaseSettings};
+use crate::repositories::bsky_repository::{BskyRepository, RetryPolicy};
+use crate::repositories::character_repository::{CharacterRepository, ScyllaCharacterRepository};
+use crate::repositories::cursor_repository::{CursorRepository, ScyllaCursorRepository};
+use crate::repositories::event_repository::{EventRepository, ScyllaEventRepository};
+use crate::repositories::experience_repository::{
+    ExperienceRepository, ScyllaExperienceRepository,
+};
+use crate::repositories::profile_batcher::BatchingProfileRepository;
+use crate::repositories::profile_cache::CachedProfileRepository;
+use crate::repositories::profile_repository::{FixtureProfileRepository, ProfileRepository};
+use scylla::statement::Consistency;
 use scylla::CachingSession;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Consistency level of each class of database operation.
+#[derive(Debug, Clone, Copy)]
+pub struct OperationConsistency {
+    pub read: Consistency,
+    pub write: Consistency,
+    pub counter: Consistency,
+}
+
+impl From<&DatabaseSettings> for OperationConsistency {
+    fn from(settings: &DatabaseSettings) -> Self {
+        Self {
+            read: settings.scylla_read_consistency.into(),
+            write: settings.scylla_write_consistency.into(),
+            counter: settings.scylla_counter_consistency.into(),
+        }
+    }
+}
 
 pub struct DatabaseRepository {
-    pub character: CharacterRepository,
-    pub event: EventRepository,
-    pub bsky: BskyRepository,
+    pub character: Arc<dyn CharacterRepository + Send + Sync>,
+    pub experience: Arc<dyn ExperienceRepository + Send + Sync>,
+    pub event: Arc<dyn EventRepository + Send + Sync>,
+    pub cursor: Arc<dyn CursorRepository + Send + Sync>,
+    pub profiles: Arc<dyn ProfileRepository + Send + Sync>,
+    pub bsky: Arc<BskyRepository>,
 }
 
 impl DatabaseRepository {
-    pub fn new(connection: Arc<CachingSession>) -> Self {
+    pub fn new(
+        connection: Arc<CachingSession>,
+        consistency: OperationConsistency,
+        settings: &BskySettings,
+    ) -> anyhow::Result<Self> {
+        let retry = RetryPolicy::from(settings);
+        let bsky = Arc::new(BskyRepository::new(settings.bsky_appview_url.clone(), retry));
+        let profiles: Arc<dyn ProfileRepository + Send + Sync> = match &settings.bsky_profile_fixtures {
+            Some(directory) => Arc::new(FixtureProfileRepository::load(directory)?),
+            None if settings.bsky_profile_batch_window_ms == 0 => bsky.clone(),
+            None => Arc::new(BatchingProfileRepository::new(
+                bsky.clone(),
+                Duration::from_millis(settings.bsky_profile_batch_window_ms),
+                settings.bsky_profile_batch_size,
+            )),
+        };
+        let profiles: Arc<dyn ProfileRepository + Send + Sync> = match settings.bsky_profile_cache_capacity {
+            0 => profiles,
+            capacity => Arc::new(CachedProfileRepository::new(
+                profiles,
+                capacity,
+                Duration::from_secs(settings.bsky_profile_cache_ttl_seconds),
+            )),
+        };
+
+        Ok(Self {
+            character: Arc::new(ScyllaCharacterRepository::new(
+                Arc::clone(&connection),
+                consistency,
+            )),
+            experience: Arc::new(ScyllaExperienceRepository::new(
+                Arc::clone(&connection),
+                consistency,
+            )),
+            event: Arc::new(ScyllaEventRepository::new(Arc::clone(&connection), consistency)),
+            cursor: Arc::new(ScyllaCursorRepository::new(Arc::clone(&connection), consistency)),
+            profiles,
+            bsky,
+        })
+    }
+
+    /// Every table backed by one [`memory::InMemoryRepository`] and no known profiles, for tests.
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let memory = Arc::new(memory::InMemoryRepository::new());
+
         Self {
-            character: CharacterRepository::new(Arc::clone(&connection)),
-            event: EventRepository::new(Arc::clone(&connection)),
-            bsky: BskyRepository::new("https://public.api.bsky.app".to_string()),
+            character: memory.clone(),
+            experience: memory.clone(),
+            event: memory.clone(),
+            cursor: memory,
+            profiles: Arc::new(FixtureProfileRepository::default()),
+            bsky: Arc::new(BskyRepository::new(
+                "http://localhost".to_string(),
+                RetryPolicy {
+                    max_retries: 0,
+                    base_delay: Duration::ZERO,
+                    max_delay: Duration::ZERO,
+                },
+            )),
         }
     }
 }
@@ -0,0 +1,296 @@
+use crate::repositories::profile_repository::{ProfileError, ProfileRepository};
+use atrium_api::app::bsky::actor::defs::ProfileViewDetailed;
+use atrium_api::types::string::AtIdentifier;
+use paris::warn;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// Most actors `app.bsky.actor.getProfiles` accepts per request.
+pub const MAX_BATCH_SIZE: usize = 25;
+
+type ProfileResponder = oneshot::Sender<Result<ProfileViewDetailed, ProfileError>>;
+
+/// Resolves single profile lookups in batches: lookups arriving within `window` of the first
+/// one are sent together as one `getProfiles` request of up to `batch_size` actors, and each
+/// waiting caller receives its own profile.
+pub struct BatchingProfileRepository {
+    requests: mpsc::UnboundedSender<(String, ProfileResponder)>,
+}
+
+impl BatchingProfileRepository {
+    /// Spawns the batching task, which runs as long as the repository is alive.
+    pub fn new(source: Arc<dyn ProfileRepository + Send + Sync>, window: Duration, batch_size: usize) -> Self {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(collect_batches(
+            source,
+            receiver,
+            window,
+            batch_size.clamp(1, MAX_BATCH_SIZE),
+        ));
+
+        Self { requests }
+    }
+}
+
+#[async_trait::async_trait]
+impl ProfileRepository for BatchingProfileRepository {
+    async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError> {
+        // a malformed actor would fail the whole getProfiles request, keep it out of the batch
+        AtIdentifier::from_str(&author).map_err(|e| ProfileError::Invalid(e.to_string()))?;
+
+        let (responder, response) = oneshot::channel();
+        self.requests
+            .send((author, responder))
+            .map_err(|_| ProfileError::Unavailable("profile batcher stopped".to_string()))?;
+
+        response
+            .await
+            .unwrap_or_else(|_| Err(ProfileError::Unavailable("profile batcher stopped".to_string())))
+    }
+}
+
+async fn collect_batches(
+    source: Arc<dyn ProfileRepository + Send + Sync>,
+    mut receiver: mpsc::UnboundedReceiver<(String, ProfileResponder)>,
+    window: Duration,
+    batch_size: usize,
+) {
+    while let Some(first) = receiver.recv().await {
+        let mut batch: HashMap<String, Vec<ProfileResponder>> = HashMap::new();
+        batch.entry(first.0).or_default().push(first.1);
+
+        let deadline = tokio::time::sleep(window);
+        tokio::pin!(deadline);
+
+        while batch.len() < batch_size {
+            tokio::select! {
+                request = receiver.recv() => match request {
+                    Some((author, responder)) => batch.entry(author).or_default().push(responder),
+                    None => break,
+                },
+                _ = &mut deadline => break,
+            }
+        }
+
+        // batches are resolved concurrently, the next one starts collecting right away
+        tokio::spawn(resolve_batch(Arc::clone(&source), batch));
+    }
+}
+
+async fn resolve_batch(
+    source: Arc<dyn ProfileRepository + Send + Sync>,
+    batch: HashMap<String, Vec<ProfileResponder>>,
+) {
+    let authors: Vec<String> = batch.keys().cloned().collect();
+
+    let profiles = match source.get_profiles(authors).await {
+        Ok(profiles) => profiles,
+        // a request rejected for one of its actors must not fail the others for good
+        Err(e) if !e.is_transient() && batch.len() > 1 => {
+            warn!("Profiles lookup failed ({}), looking the {} profiles up one by one", e, batch.len());
+            resolve_one_by_one(source, batch).await;
+            return;
+        }
+        Err(e) => {
+            for responder in batch.into_values().flatten() {
+                let _ = responder.send(Err(e.clone()));
+            }
+            return;
+        }
+    };
+
+    // callers may have asked by DID or by handle
+    let mut by_actor = HashMap::new();
+    for profile in &profiles {
+        by_actor.insert(profile.did.as_str().to_string(), profile);
+        by_actor.insert(profile.handle.as_str().to_string(), profile);
+    }
+
+    for (author, responders) in batch {
+        let result = match by_actor.get(&author) {
+            Some(profile) => Ok((*profile).clone()),
+            None => Err(ProfileError::NotFound {
+                author,
+                reason: "not returned by getProfiles".to_string(),
+            }),
+        };
+
+        for responder in responders {
+            let _ = responder.send(result.clone());
+        }
+    }
+}
+
+async fn resolve_one_by_one(
+    source: Arc<dyn ProfileRepository + Send + Sync>,
+    batch: HashMap<String, Vec<ProfileResponder>>,
+) {
+    let lookups = batch.into_iter().map(|(author, responders)| {
+        let source = Arc::clone(&source);
+        async move {
+            let result = source.get_author_profile(author).await;
+            for responder in responders {
+                let _ = responder.send(result.clone());
+            }
+        }
+    });
+
+    futures::future::join_all(lookups).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records the size of every `getProfiles` request and every single lookup; knows every
+    /// user but `did:plc:missing*`, and rejects requests including `did:plc:rejected*`.
+    #[derive(Default)]
+    struct RecordingProfiles {
+        batches: Mutex<Vec<usize>>,
+        single_lookups: Mutex<Vec<String>>,
+        unavailable: bool,
+    }
+
+    impl RecordingProfiles {
+        fn profile(author: &str) -> Result<ProfileViewDetailed, ProfileError> {
+            if author.starts_with("did:plc:rejected") {
+                return Err(ProfileError::NotFound {
+                    author: author.to_string(),
+                    reason: "400 Account has been suspended".to_string(),
+                });
+            }
+
+            let (did, handle) = match author.strip_suffix(".test") {
+                Some(name) => (format!("did:plc:{}", name), author.to_string()),
+                None => (author.to_string(), format!("{}.test", author.trim_start_matches("did:plc:"))),
+            };
+            Ok(serde_json::from_value(json!({ "did": did, "handle": handle })).unwrap())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ProfileRepository for RecordingProfiles {
+        async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError> {
+            self.single_lookups.lock().unwrap().push(author.clone());
+            Self::profile(&author)
+        }
+
+        async fn get_profiles(&self, authors: Vec<String>) -> Result<Vec<ProfileViewDetailed>, ProfileError> {
+            self.batches.lock().unwrap().push(authors.len());
+            if self.unavailable {
+                return Err(ProfileError::RateLimited);
+            }
+
+            authors
+                .iter()
+                .filter(|author| !author.starts_with("did:plc:missing"))
+                .map(|author| Self::profile(author))
+                .collect()
+        }
+    }
+
+    fn batcher(source: &Arc<RecordingProfiles>) -> BatchingProfileRepository {
+        BatchingProfileRepository::new(source.clone(), Duration::from_millis(20), MAX_BATCH_SIZE)
+    }
+
+    #[tokio::test]
+    async fn burst_of_lookups_is_resolved_in_batches_of_25() {
+        let source = Arc::new(RecordingProfiles::default());
+        let batcher = batcher(&source);
+
+        let lookups = (0..60).map(|user| batcher.get_author_profile(format!("did:plc:user{}", user)));
+        let profiles = futures::future::join_all(lookups).await;
+
+        for (user, profile) in profiles.into_iter().enumerate() {
+            assert_eq!(profile.unwrap().did.as_str(), format!("did:plc:user{}", user));
+        }
+        assert_eq!(*source.batches.lock().unwrap(), vec![25, 25, 10]);
+    }
+
+    #[tokio::test]
+    async fn duplicate_lookups_share_a_slot_in_the_batch() {
+        let source = Arc::new(RecordingProfiles::default());
+        let batcher = batcher(&source);
+
+        let (by_did, again, by_handle) = tokio::join!(
+            batcher.get_author_profile("did:plc:alice".to_string()),
+            batcher.get_author_profile("did:plc:alice".to_string()),
+            batcher.get_author_profile("alice.test".to_string()),
+        );
+
+        assert_eq!(by_did.unwrap().handle.as_str(), "alice.test");
+        assert_eq!(again.unwrap().handle.as_str(), "alice.test");
+        assert_eq!(by_handle.unwrap().did.as_str(), "did:plc:alice");
+        assert_eq!(*source.batches.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn profiles_missing_from_the_response_are_not_found() {
+        let source = Arc::new(RecordingProfiles::default());
+        let batcher = batcher(&source);
+
+        let (found, missing) = tokio::join!(
+            batcher.get_author_profile("did:plc:alice".to_string()),
+            batcher.get_author_profile("did:plc:missing".to_string()),
+        );
+
+        assert!(found.is_ok());
+        assert!(matches!(missing, Err(ProfileError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn failed_batch_fails_every_waiting_lookup() {
+        let source = Arc::new(RecordingProfiles {
+            unavailable: true,
+            ..Default::default()
+        });
+        let batcher = batcher(&source);
+
+        let (first, second) = tokio::join!(
+            batcher.get_author_profile("did:plc:alice".to_string()),
+            batcher.get_author_profile("did:plc:bob".to_string()),
+        );
+
+        assert!(matches!(first, Err(ProfileError::RateLimited)));
+        assert!(matches!(second, Err(ProfileError::RateLimited)));
+        assert_eq!(*source.batches.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn malformed_actors_are_kept_out_of_the_batch() {
+        let source = Arc::new(RecordingProfiles::default());
+        let batcher = batcher(&source);
+
+        let (malformed, found) = tokio::join!(
+            batcher.get_author_profile("not an actor".to_string()),
+            batcher.get_author_profile("did:plc:alice".to_string()),
+        );
+
+        assert!(matches!(malformed, Err(ProfileError::Invalid(_))));
+        assert!(found.is_ok());
+        assert_eq!(*source.batches.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn a_rejected_batch_is_looked_up_one_by_one() {
+        let source = Arc::new(RecordingProfiles::default());
+        let batcher = batcher(&source);
+
+        let (rejected, alice, bob) = tokio::join!(
+            batcher.get_author_profile("did:plc:rejected".to_string()),
+            batcher.get_author_profile("did:plc:alice".to_string()),
+            batcher.get_author_profile("did:plc:bob".to_string()),
+        );
+
+        assert!(matches!(rejected, Err(ProfileError::NotFound { .. })));
+        assert_eq!(alice.unwrap().did.as_str(), "did:plc:alice");
+        assert_eq!(bob.unwrap().did.as_str(), "did:plc:bob");
+        assert_eq!(*source.batches.lock().unwrap(), vec![3]);
+        assert_eq!(source.single_lookups.lock().unwrap().len(), 3);
+    }
+}
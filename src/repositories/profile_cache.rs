@@ -0,0 +1,289 @@
+use crate::repositories::profile_repository::{ProfileError, ProfileRepository};
+use atrium_api::app::bsky::actor::defs::ProfileViewDetailed;
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type ProfileLookup = Shared<BoxFuture<'static, Result<ProfileViewDetailed, ProfileError>>>;
+
+/// Hit and miss counters of the profile cache, served by `GET /status/profiles`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileCacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Lookups that waited for an identical lookup already in flight instead of fetching.
+    pub coalesced: u64,
+    pub hit_rate: f64,
+}
+
+/// An LRU cache with a time to live in front of a [`ProfileRepository`].
+///
+/// Concurrent lookups of the same uncached profile are coalesced into one request, so a burst
+/// of events from a new user fetches its profile once. Only found profiles are cached.
+pub struct CachedProfileRepository {
+    state: Arc<CacheState>,
+}
+
+struct CacheState {
+    source: Arc<dyn ProfileRepository + Send + Sync>,
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<LruEntries>,
+    in_flight: Mutex<HashMap<String, ProfileLookup>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+#[derive(Default)]
+struct LruEntries {
+    profiles: HashMap<String, CacheEntry>,
+    /// Keys by last use, the least recently used first.
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+struct CacheEntry {
+    profile: ProfileViewDetailed,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+impl CachedProfileRepository {
+    pub fn new(source: Arc<dyn ProfileRepository + Send + Sync>, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            state: Arc::new(CacheState {
+                source,
+                capacity,
+                ttl,
+                entries: Mutex::new(LruEntries::default()),
+                in_flight: Mutex::new(HashMap::new()),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                coalesced: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ProfileRepository for CachedProfileRepository {
+    async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError> {
+        let state = &self.state;
+
+        if let Some(profile) = state.entries.lock().unwrap().get(&author) {
+            state.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(profile);
+        }
+
+        let lookup = {
+            let mut in_flight = state.in_flight.lock().unwrap();
+            match in_flight.get(&author) {
+                Some(lookup) => {
+                    state.coalesced.fetch_add(1, Ordering::Relaxed);
+                    lookup.clone()
+                }
+                None => {
+                    state.misses.fetch_add(1, Ordering::Relaxed);
+                    let lookup = CacheState::lookup(Arc::clone(state), author.clone());
+                    in_flight.insert(author, lookup.clone());
+                    lookup
+                }
+            }
+        };
+
+        lookup.await
+    }
+
+    fn cache_stats(&self) -> Option<ProfileCacheStats> {
+        let state = &self.state;
+        let hits = state.hits.load(Ordering::Relaxed);
+        let misses = state.misses.load(Ordering::Relaxed);
+        let coalesced = state.coalesced.load(Ordering::Relaxed);
+        let lookups = hits + misses + coalesced;
+
+        Some(ProfileCacheStats {
+            entries: state.entries.lock().unwrap().profiles.len(),
+            hits,
+            misses,
+            coalesced,
+            hit_rate: if lookups == 0 { 0.0 } else { (hits + coalesced) as f64 / lookups as f64 },
+        })
+    }
+}
+
+impl CacheState {
+    /// Fetches the profile once for every caller waiting on it; whoever polls it first runs it.
+    fn lookup(state: Arc<Self>, author: String) -> ProfileLookup {
+        async move {
+            let result = state.source.get_author_profile(author.clone()).await;
+
+            if let Ok(profile) = &result {
+                let expires_at = Instant::now() + state.ttl;
+                state
+                    .entries
+                    .lock()
+                    .unwrap()
+                    .insert(author.clone(), profile.clone(), expires_at, state.capacity);
+            }
+            state.in_flight.lock().unwrap().remove(&author);
+
+            result
+        }
+        .boxed()
+        .shared()
+    }
+}
+
+impl LruEntries {
+    fn get(&mut self, key: &str) -> Option<ProfileViewDetailed> {
+        let entry = self.profiles.get_mut(key)?;
+
+        if entry.expires_at <= Instant::now() {
+            self.recency.remove(&entry.last_used);
+            self.profiles.remove(key);
+            return None;
+        }
+
+        self.tick += 1;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(self.tick, key.to_string());
+        entry.last_used = self.tick;
+
+        Some(entry.profile.clone())
+    }
+
+    fn insert(&mut self, key: String, profile: ProfileViewDetailed, expires_at: Instant, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+
+        if let Some(previous) = self.profiles.remove(&key) {
+            self.recency.remove(&previous.last_used);
+        }
+        while self.profiles.len() >= capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            self.profiles.remove(&evicted);
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.profiles.insert(
+            key,
+            CacheEntry {
+                profile,
+                expires_at,
+                last_used: self.tick,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Counts the lookups reaching the AppView; each one takes a little while.
+    #[derive(Default)]
+    struct CountingProfiles {
+        lookups: AtomicU64,
+    }
+
+    #[async_trait::async_trait]
+    impl ProfileRepository for CountingProfiles {
+        async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+
+            if author.starts_with("did:plc:missing") {
+                return Err(ProfileError::NotFound {
+                    author,
+                    reason: "Profile not found".to_string(),
+                });
+            }
+
+            Ok(serde_json::from_value(json!({ "did": author, "handle": "user.test" })).unwrap())
+        }
+    }
+
+    fn cached(capacity: usize, ttl: Duration) -> (Arc<CountingProfiles>, CachedProfileRepository) {
+        let source = Arc::new(CountingProfiles::default());
+        let cache = CachedProfileRepository::new(source.clone(), capacity, ttl);
+
+        (source, cache)
+    }
+
+    #[tokio::test]
+    async fn repeated_lookups_are_served_from_the_cache() {
+        let (source, cache) = cached(10, Duration::from_secs(60));
+
+        for _ in 0..3 {
+            cache.get_author_profile("did:plc:first".to_string()).await.unwrap();
+        }
+
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 1);
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn concurrent_lookups_of_a_new_profile_are_coalesced() {
+        let (source, cache) = cached(10, Duration::from_secs(60));
+
+        let lookups = (0..20).map(|_| cache.get_author_profile("did:plc:burst".to_string()));
+        let profiles = futures::future::join_all(lookups).await;
+
+        assert!(profiles.iter().all(|profile| profile.is_ok()));
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 1);
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!((stats.misses, stats.coalesced), (1, 19));
+    }
+
+    #[tokio::test]
+    async fn missing_profiles_are_not_cached() {
+        let (source, cache) = cached(10, Duration::from_secs(60));
+
+        for _ in 0..2 {
+            let missing = cache.get_author_profile("did:plc:missing".to_string()).await;
+            assert!(matches!(missing, Err(ProfileError::NotFound { .. })));
+        }
+
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_profiles_are_fetched_again() {
+        let (source, cache) = cached(10, Duration::from_millis(50));
+
+        cache.get_author_profile("did:plc:first".to_string()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        cache.get_author_profile("did:plc:first".to_string()).await.unwrap();
+
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_profile_is_evicted() {
+        let (source, cache) = cached(2, Duration::from_secs(60));
+
+        cache.get_author_profile("did:plc:first".to_string()).await.unwrap();
+        cache.get_author_profile("did:plc:second".to_string()).await.unwrap();
+        cache.get_author_profile("did:plc:first".to_string()).await.unwrap();
+        cache.get_author_profile("did:plc:third".to_string()).await.unwrap(); // evicts second
+
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 3);
+        cache.get_author_profile("did:plc:first".to_string()).await.unwrap();
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 3);
+        cache.get_author_profile("did:plc:second".to_string()).await.unwrap();
+        assert_eq!(source.lookups.load(Ordering::SeqCst), 4);
+        assert_eq!(cache.cache_stats().unwrap().entries, 2);
+    }
+}
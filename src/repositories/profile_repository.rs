@@ -0,0 +1,195 @@
+use crate::repositories::profile_cache::ProfileCacheStats;
+use anyhow::Context;
+use atrium_api::app::bsky::actor::defs::ProfileViewDetailed;
+use paris::info;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Why a profile couldn't be fetched.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ProfileError {
+    /// The account doesn't exist (anymore), or was deactivated or suspended.
+    #[error("profile {author} is not available: {reason}")]
+    NotFound { author: String, reason: String },
+    /// The AppView answered with 429 Too Many Requests.
+    #[error("rate limited by the AppView")]
+    RateLimited,
+    /// Network errors, timeouts and 5xx responses.
+    #[error("AppView request failed: {0}")]
+    Unavailable(String),
+    /// Responses that will never turn into a profile, e.g. malformed identifiers or bodies.
+    #[error("invalid profile request or response: {0}")]
+    Invalid(String),
+}
+
+impl ProfileError {
+    /// Whether the same lookup may succeed later.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ProfileError::RateLimited | ProfileError::Unavailable(_))
+    }
+}
+
+/// Where character bootstraps get a user's Bluesky profile from.
+#[async_trait::async_trait]
+pub trait ProfileRepository {
+    /// Looks up a profile by DID or handle.
+    async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError>;
+
+    /// Looks up several profiles at once. Profiles that don't exist are missing from the result.
+    async fn get_profiles(&self, authors: Vec<String>) -> Result<Vec<ProfileViewDetailed>, ProfileError> {
+        let mut profiles = Vec::with_capacity(authors.len());
+        for author in authors {
+            match self.get_author_profile(author).await {
+                Ok(profile) => profiles.push(profile),
+                Err(ProfileError::NotFound { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(profiles)
+    }
+
+    /// Hit and miss counters, for sources with a cache.
+    fn cache_stats(&self) -> Option<ProfileCacheStats> {
+        None
+    }
+}
+
+/// Profiles read from JSON files instead of the AppView, for tests and air-gapped environments.
+///
+/// Every `*.json` file of the directory holds one profile, as returned by
+/// `app.bsky.actor.getProfile`. Profiles are found by DID or handle.
+#[derive(Default)]
+pub struct FixtureProfileRepository {
+    profiles: HashMap<String, ProfileViewDetailed>,
+}
+
+impl FixtureProfileRepository {
+    pub fn load(directory: &Path) -> anyhow::Result<Self> {
+        let mut fixtures = Self::default();
+        let mut loaded = 0;
+
+        let entries = std::fs::read_dir(directory)
+            .with_context(|| format!("failed to read profile fixtures from {}", directory.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let profile = serde_json::from_str(&content)
+                .with_context(|| format!("{} is not a valid profile", path.display()))?;
+            fixtures.insert(profile);
+            loaded += 1;
+        }
+
+        info!("Loaded {} fixture profiles from {}", loaded, directory.display());
+        Ok(fixtures)
+    }
+
+    pub fn insert(&mut self, profile: ProfileViewDetailed) {
+        self.profiles
+            .insert(profile.handle.as_str().to_string(), profile.clone());
+        self.profiles.insert(profile.did.as_str().to_string(), profile);
+    }
+}
+
+#[async_trait::async_trait]
+impl ProfileRepository for FixtureProfileRepository {
+    async fn get_author_profile(&self, author: String) -> Result<ProfileViewDetailed, ProfileError> {
+        self.profiles
+            .get(&author)
+            .cloned()
+            .ok_or_else(|| ProfileError::NotFound {
+                author,
+                reason: "no fixture profile".to_string(),
+            })
+    }
+}
+
+/// An AppView that is always down, for tests.
+#[cfg(test)]
+pub struct UnavailableProfileRepository;
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl ProfileRepository for UnavailableProfileRepository {
+    async fn get_author_profile(&self, _author: String) -> Result<ProfileViewDetailed, ProfileError> {
+        Err(ProfileError::Unavailable("503 Service Unavailable".to_string()))
+    }
+}
+
+/// An AppView that doesn't answer until released, and is down from then on, for tests.
+#[cfg(test)]
+pub struct StalledProfileRepository {
+    released: tokio::sync::watch::Sender<bool>,
+}
+
+#[cfg(test)]
+impl StalledProfileRepository {
+    pub fn new() -> Self {
+        Self {
+            released: tokio::sync::watch::Sender::new(false),
+        }
+    }
+
+    pub fn release(&self) {
+        self.released.send_replace(true);
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl ProfileRepository for StalledProfileRepository {
+    async fn get_author_profile(&self, _author: String) -> Result<ProfileViewDetailed, ProfileError> {
+        self.released.subscribe().wait_for(|released| *released).await.ok();
+
+        Err(ProfileError::Unavailable("503 Service Unavailable".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> FixtureProfileRepository {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/profiles");
+        FixtureProfileRepository::load(&directory).unwrap()
+    }
+
+    #[tokio::test]
+    async fn fixture_profiles_are_found_by_did_and_handle() {
+        let fixtures = fixtures();
+
+        let by_did = fixtures
+            .get_author_profile("did:plc:alicefixture00000000000".to_string())
+            .await
+            .unwrap();
+        let by_handle = fixtures
+            .get_author_profile("alice.test".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(by_did.handle.as_str(), "alice.test");
+        assert_eq!(by_did.posts_count, Some(42));
+        assert_eq!(by_handle.did, by_did.did);
+
+        let missing = fixtures.get_author_profile("bob.test".to_string()).await;
+        assert!(matches!(missing, Err(ProfileError::NotFound { .. })));
+    }
+
+    #[test]
+    fn invalid_fixture_is_reported() {
+        let directory = std::env::temp_dir().join(format!("profile-fixtures-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("broken.json"), "{ \"handle\": 1 }").unwrap();
+
+        let error = FixtureProfileRepository::load(&directory).err().unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(format!("{:#}", error).contains("broken.json is not a valid profile"));
+    }
+}
@@ -0,0 +1,568 @@
+use crate::events::context::EventContext;
+use crate::events::facets::normalize_hashtag;
+use crate::events::AppBskyEventRecord;
+use crate::leveling::curves::{CurveConfig, LevelCurve};
+use anyhow::{bail, Context};
+use atrium_api::types::string::Nsid;
+use paris::{error, info};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Experience rules per collection and the leveling curve, loaded from a TOML file.
+///
+/// ```toml
+/// [curve]
+/// type = "linear"
+/// base = 50
+/// step = 100
+///
+/// [collections."app.bsky.feed.post"]
+/// base = 30
+///
+/// [[collections."app.bsky.feed.post".bonuses]]
+/// condition = "has_image"
+/// experience = 100
+///
+/// [[collections."app.bsky.feed.post".bonuses]]
+/// condition = "min_length"
+/// length = 200
+/// experience = 20
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperienceRules {
+    #[serde(default)]
+    pub curve: CurveConfig,
+    #[serde(default)]
+    pub collections: HashMap<String, CollectionRules>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionRules {
+    /// Experience granted for every event of the collection.
+    pub base: i32,
+    /// Extra experience granted when the condition matches the event.
+    #[serde(default)]
+    pub bonuses: Vec<BonusRule>,
+    /// Experience granted to the author of the liked, reposted, replied to or quoted record.
+    #[serde(default)]
+    pub received: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BonusRule {
+    #[serde(flatten)]
+    pub condition: Condition,
+    pub experience: i32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum Condition {
+    HasImage,
+    /// Every image and video has meaningful alt text.
+    HasAltText,
+    /// At least `ratio` (0 to 1) of the images and video have meaningful alt text.
+    AltTextCoverage { ratio: f32 },
+    MinLength { length: usize },
+    MaxLength { length: usize },
+    Language { language: String },
+    /// Top-level posts, starting a thread.
+    Root,
+    Reply,
+    Quote,
+    /// Posts sharing a link card.
+    HasLink,
+    HasVideo,
+    /// The post is tagged with `tag`, with or without the leading `#` and in any case.
+    Hashtag { tag: String },
+    /// At most `count` distinct hashtags.
+    MaxHashtags { count: usize },
+    HasMention,
+    /// At most `count` distinct accounts mentioned.
+    MaxMentions { count: usize },
+    /// At most `count` distinct links in the text.
+    MaxLinks { count: usize },
+}
+
+impl Default for ExperienceRules {
+    /// The rules the game shipped with before they were configurable.
+    fn default() -> Self {
+        let collections = HashMap::from([
+            (
+                AppBskyEventRecord::Post.to_string(),
+                CollectionRules {
+                    base: 30,
+                    bonuses: vec![
+                        BonusRule {
+                            condition: Condition::HasImage,
+                            experience: 100,
+                        },
+                        BonusRule {
+                            condition: Condition::HasAltText,
+                            experience: 50,
+                        },
+                    ],
+                    received: 10,
+                },
+            ),
+            (
+                AppBskyEventRecord::Like.to_string(),
+                CollectionRules {
+                    base: 10,
+                    bonuses: vec![],
+                    received: 5,
+                },
+            ),
+            (
+                AppBskyEventRecord::Repost.to_string(),
+                CollectionRules {
+                    base: 10,
+                    bonuses: vec![],
+                    received: 10,
+                },
+            ),
+            (
+                AppBskyEventRecord::Follow.to_string(),
+                CollectionRules {
+                    base: 5,
+                    bonuses: vec![],
+                    received: 0,
+                },
+            ),
+            (
+                AppBskyEventRecord::Block.to_string(),
+                CollectionRules {
+                    base: 0,
+                    bonuses: vec![],
+                    received: 0,
+                },
+            ),
+            (
+                AppBskyEventRecord::ListItem.to_string(),
+                CollectionRules {
+                    base: 5,
+                    bonuses: vec![],
+                    received: 0,
+                },
+            ),
+            (
+                AppBskyEventRecord::StarterPack.to_string(),
+                CollectionRules {
+                    base: 50,
+                    bonuses: vec![],
+                    received: 0,
+                },
+            ),
+        ]);
+
+        Self {
+            curve: CurveConfig::default(),
+            collections,
+        }
+    }
+}
+
+impl ExperienceRules {
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        let rules: ExperienceRules = toml::from_str(content)?;
+        rules.validate()?;
+
+        Ok(rules)
+    }
+
+    /// Experience granted for an event of `collection`; zero for collections without rules.
+    pub fn experience_for(&self, collection: &str, context: &EventContext) -> i32 {
+        let Some(rules) = self.collections.get(collection) else {
+            return 0;
+        };
+
+        rules
+            .bonuses
+            .iter()
+            .filter(|bonus| bonus.condition.matches(context))
+            .fold(rules.base, |exp, bonus| exp.saturating_add(bonus.experience))
+    }
+
+    pub fn curve(&self) -> &dyn LevelCurve {
+        self.curve.as_curve()
+    }
+
+    /// Experience granted to the recipient of engagement through an event of `collection`.
+    pub fn received_experience(&self, collection: &str) -> i32 {
+        self.collections
+            .get(collection)
+            .map(|rules| rules.received)
+            .unwrap_or_default()
+    }
+
+    /// Base experience of `collection`, without any bonuses.
+    pub fn base_experience(&self, collection: &str) -> i64 {
+        self.collections
+            .get(collection)
+            .map(|rules| rules.base as i64)
+            .unwrap_or_default()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let post = AppBskyEventRecord::Post.to_string();
+
+        self.curve.validate()?;
+
+        for (collection, rules) in &self.collections {
+            Nsid::new(collection.clone())
+                .map_err(|e| anyhow::anyhow!("invalid collection NSID {collection:?}: {e}"))?;
+
+            if rules.base < 0 {
+                bail!("{collection}: base experience must not be negative");
+            }
+
+            if rules.received < 0 {
+                bail!("{collection}: received experience must not be negative");
+            }
+
+            let engages = [AppBskyEventRecord::Post, AppBskyEventRecord::Like, AppBskyEventRecord::Repost]
+                .iter()
+                .any(|event_record| event_record.to_string() == *collection);
+            if rules.received != 0 && !engages {
+                bail!("{collection}: received experience only applies to posts, likes and reposts");
+            }
+
+            for bonus in &rules.bonuses {
+                // every condition so far inspects post content
+                if *collection != post {
+                    bail!(
+                        "{collection}: condition {:?} only applies to {post}",
+                        bonus.condition
+                    );
+                }
+
+                if bonus.experience < 0 {
+                    bail!("{collection}: bonus experience must not be negative");
+                }
+
+                match &bonus.condition {
+                    Condition::MinLength { length } | Condition::MaxLength { length }
+                        if *length == 0 =>
+                    {
+                        bail!("{collection}: length thresholds must be greater than zero");
+                    }
+                    Condition::AltTextCoverage { ratio } if !(*ratio > 0.0 && *ratio <= 1.0) => {
+                        bail!("{collection}: alt text coverage must be greater than 0 and at most 1");
+                    }
+                    Condition::Language { language } if language.is_empty() => {
+                        bail!("{collection}: language must not be empty");
+                    }
+                    Condition::Hashtag { tag } if normalize_hashtag(tag).is_empty() => {
+                        bail!("{collection}: hashtag must not be empty");
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Condition {
+    fn matches(&self, context: &EventContext) -> bool {
+        let EventContext::Post(post) = context else {
+            return false;
+        };
+
+        match self {
+            Condition::HasImage => post.has_image,
+            Condition::HasAltText => post.accessibility.fully_described(),
+            Condition::AltTextCoverage { ratio } => {
+                !post.accessibility.media.is_empty() && post.accessibility.coverage() >= *ratio
+            }
+            Condition::MinLength { length } => post.length >= *length,
+            Condition::MaxLength { length } => post.length <= *length,
+            Condition::Language { language } => {
+                post.langs.iter().any(|lang| language_matches(lang, language))
+            }
+            Condition::Root => post.reply.is_none(),
+            Condition::Reply => post.reply.is_some(),
+            Condition::Quote => post.quote_uri.is_some(),
+            Condition::HasLink => post.link_uri.is_some(),
+            Condition::HasVideo => post.has_video,
+            Condition::Hashtag { tag } => post.facets.has_hashtag(tag),
+            Condition::MaxHashtags { count } => post.facets.hashtags.len() <= *count,
+            Condition::HasMention => !post.facets.mentions.is_empty(),
+            Condition::MaxMentions { count } => post.facets.mentions.len() <= *count,
+            Condition::MaxLinks { count } => post.facets.links.len() <= *count,
+        }
+    }
+}
+
+/// Whether the BCP 47 tag `lang` of a post is `language`, or one of its regional variants:
+/// `en` matches `en-US`, `en-us` and `en`.
+fn language_matches(lang: &str, language: &str) -> bool {
+    lang.eq_ignore_ascii_case(language)
+        || lang
+            .split_once('-')
+            .is_some_and(|(primary, _)| primary.eq_ignore_ascii_case(language))
+}
+
+/// Holds the active rules and swaps them atomically on reload.
+pub struct RulesStore {
+    path: Option<PathBuf>,
+    rules: RwLock<Arc<ExperienceRules>>,
+}
+
+impl RulesStore {
+    /// Loads the rules from `path`, or the built-in defaults when no path is configured.
+    pub fn load(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let rules = match &path {
+            Some(path) => read_rules(path)?,
+            None => ExperienceRules::default(),
+        };
+
+        Ok(Self {
+            path,
+            rules: RwLock::new(Arc::new(rules)),
+        })
+    }
+
+    pub fn current(&self) -> Arc<ExperienceRules> {
+        Arc::clone(&self.rules.read().unwrap())
+    }
+
+    /// Re-reads the rules file. Invalid files are reported and the previous rules are kept.
+    pub fn reload(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        match read_rules(path) {
+            Ok(rules) => {
+                *self.rules.write().unwrap() = Arc::new(rules);
+                info!("Reloaded experience rules from {}", path.display());
+            }
+            Err(e) => error!("Keeping previous experience rules: {:#}", e),
+        }
+    }
+}
+
+fn read_rules(path: &Path) -> anyhow::Result<ExperienceRules> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read rules file {}", path.display()))?;
+
+    ExperienceRules::from_toml(&content)
+        .with_context(|| format!("invalid rules file {}", path.display()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::accessibility::AccessibilityContext;
+    use crate::events::context::{PostContext, ReplyContext};
+    use crate::events::facets::FacetsContext;
+
+    fn post() -> PostContext {
+        PostContext {
+            text: "hello world".to_string(),
+            length: 11,
+            has_image: false,
+            accessibility: AccessibilityContext::default(),
+            has_video: false,
+            langs: vec![],
+            reply: None,
+            quote_uri: None,
+            link_uri: None,
+            facets: FacetsContext::default(),
+        }
+    }
+
+    #[test]
+    fn kinds_of_posts_are_scored_separately() {
+        let rules = ExperienceRules::from_toml(
+            r#"
+            [collections."app.bsky.feed.post"]
+            base = 10
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "root"
+            experience = 20
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "quote"
+            experience = 15
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "has_link"
+            experience = 5
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "has_video"
+            experience = 40
+            "#,
+        )
+        .unwrap();
+        let collection = "app.bsky.feed.post";
+        let score = |post: PostContext| rules.experience_for(collection, &EventContext::Post(Box::new(post)));
+
+        let reply = PostContext {
+            reply: Some(ReplyContext {
+                root_uri: "at://did:plc:root/app.bsky.feed.post/1".to_string(),
+                root_did: Some("did:plc:root".to_string()),
+                parent_uri: "at://did:plc:root/app.bsky.feed.post/1".to_string(),
+                parent_did: Some("did:plc:root".to_string()),
+            }),
+            ..post()
+        };
+        let quote = PostContext {
+            quote_uri: Some("at://did:plc:root/app.bsky.feed.post/1".to_string()),
+            ..post()
+        };
+        let link = PostContext {
+            link_uri: Some("https://example.com".to_string()),
+            ..post()
+        };
+        let video = PostContext {
+            has_video: true,
+            ..post()
+        };
+
+        assert_eq!(score(post()), 30);
+        assert_eq!(score(reply), 10);
+        assert_eq!(score(quote), 45);
+        assert_eq!(score(link), 35);
+        assert_eq!(score(video), 70);
+    }
+
+    #[test]
+    fn alt_text_coverage_needs_the_ratio_of_described_media() {
+        let rules = ExperienceRules::from_toml(
+            r#"
+            [collections."app.bsky.feed.post"]
+            base = 30
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "alt_text_coverage"
+            ratio = 0.5
+            experience = 25
+            "#,
+        )
+        .unwrap();
+        let score = |alt_texts: &[&str]| {
+            let post = PostContext {
+                has_image: true,
+                accessibility: AccessibilityContext::from_alt_texts(alt_texts.iter().copied()),
+                ..post()
+            };
+            rules.experience_for("app.bsky.feed.post", &EventContext::Post(Box::new(post)))
+        };
+
+        assert_eq!(score(&["A cat asleep on a keyboard", "A second cat"]), 55);
+        assert_eq!(score(&["A cat asleep on a keyboard", "img"]), 55);
+        assert_eq!(score(&["A cat asleep on a keyboard", "img", ""]), 30);
+        assert_eq!(score(&[]), 30);
+    }
+
+    #[test]
+    fn alt_text_coverage_ratio_is_validated() {
+        let error = ExperienceRules::from_toml(
+            r#"
+            [collections."app.bsky.feed.post"]
+            base = 30
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "alt_text_coverage"
+            ratio = 1.5
+            experience = 25
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("alt text coverage"));
+    }
+
+    #[test]
+    fn community_hashtags_are_rewarded_without_mention_spam() {
+        let rules = ExperienceRules::from_toml(
+            r##"
+            [collections."app.bsky.feed.post"]
+            base = 30
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "hashtag"
+            tag = "#BlueskyGame"
+            experience = 20
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "max_mentions"
+            count = 2
+            experience = 10
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "language"
+            language = "en"
+            experience = 5
+            "##,
+        )
+        .unwrap();
+        let score = |hashtags: &[&str], mentions: usize, langs: &[&str]| {
+            let post = PostContext {
+                langs: langs.iter().map(|lang| lang.to_string()).collect(),
+                facets: FacetsContext {
+                    hashtags: hashtags.iter().map(|tag| tag.to_string()).collect(),
+                    mentions: (0..mentions).map(|i| format!("did:plc:friend{i}")).collect(),
+                    links: vec![],
+                },
+                ..post()
+            };
+            rules.experience_for("app.bsky.feed.post", &EventContext::Post(Box::new(post)))
+        };
+
+        assert_eq!(score(&["blueskygame"], 0, &["en-GB"]), 65);
+        assert_eq!(score(&["blueskygame"], 3, &["fr"]), 50);
+        assert_eq!(score(&[], 2, &["EN"]), 45);
+        assert_eq!(score(&[], 5, &["eng"]), 30);
+    }
+
+    #[test]
+    fn hashtags_must_not_be_empty() {
+        let error = ExperienceRules::from_toml(
+            r##"
+            [collections."app.bsky.feed.post"]
+            base = 30
+
+            [[collections."app.bsky.feed.post".bonuses]]
+            condition = "hashtag"
+            tag = "#"
+            experience = 20
+            "##,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("hashtag"));
+    }
+
+    #[test]
+    fn negative_experience_is_rejected() {
+        let rules = |base: i32, bonus: i32| {
+            ExperienceRules::from_toml(&format!(
+                r#"
+                [collections."app.bsky.feed.post"]
+                base = {base}
+
+                [[collections."app.bsky.feed.post".bonuses]]
+                condition = "has_image"
+                experience = {bonus}
+                "#
+            ))
+        };
+
+        let error = rules(-30, 10).unwrap_err();
+        assert!(error.to_string().contains("base experience must not be negative"));
+        let error = rules(30, -10).unwrap_err();
+        assert!(error.to_string().contains("bonus experience must not be negative"));
+        assert!(rules(0, 0).is_ok());
+    }
+}